@@ -1,6 +1,9 @@
 use rustc_version::{version_meta, Channel};
 
 fn main() {
+    for channel in ["RUSTC_IS_STABLE", "RUSTC_IS_BETA", "RUSTC_IS_NIGHTLY", "RUSTC_IS_DEV"] {
+        println!("cargo::rustc-check-cfg=cfg({channel})");
+    }
     // Set cfg flags depending on release channel
     match version_meta().unwrap().channel {
         Channel::Stable => {
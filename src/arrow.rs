@@ -0,0 +1,105 @@
+use std::{io::Write, sync::Arc};
+
+use arrow_array::{ArrayRef, Float64Array, Int32Array, RecordBatch, StringArray};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+
+use crate::{error::{QvdError, QvdErrorKind}, types::{CellValue, Column, ColumnType, QvdDocument}};
+
+impl QvdDocument {
+    /// Converts the document to an Arrow `RecordBatch` with one array per
+    /// column. Columns holding only `Int` values become `Int32`, columns of
+    /// numbers become `Float64` and all others `Utf8`, with numbers rendered
+    /// as text. A field is nullable if any of its rows is null.
+    pub fn to_arrow(&self) -> Result<RecordBatch, QvdError> {
+        let (fields, arrays): (Vec<_>, Vec<_>) = self.columns().iter().map(|col| {
+            let nullable = col.null_mask().contains(&false);
+            let (data_type, array) = column_array(col);
+            (Field::new(col.header.0.clone(), data_type, nullable), array)
+        }).unzip();
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+            .map_err(|err| QvdError::new(QvdErrorKind::SchemaMismatch, err.to_string()))
+    }
+
+    /// Writes the document as an Arrow IPC stream of one record batch, as
+    /// read by pyarrow or DuckDB. Column types follow `to_arrow`.
+    pub fn write_arrow_ipc<W: Write>(&self, writer: W) -> Result<(), QvdError> {
+        let batch = self.to_arrow()?;
+        let ipc_error = |err: ArrowError| QvdError::new(QvdErrorKind::WriteFile, err.to_string());
+        let mut writer = StreamWriter::try_new(writer, &batch.schema()).map_err(ipc_error)?;
+        writer.write(&batch).map_err(ipc_error)?;
+        writer.finish().map_err(ipc_error)
+    }
+}
+
+fn column_array(col: &Column) -> (DataType, ArrayRef) {
+    let values = col.as_values();
+    match col.dtype() {
+        ColumnType::Int => {
+            let array: Int32Array = values.iter().map(|value| match value {
+                CellValue::Int(i) => Some(*i),
+                _ => None,
+            }).collect();
+            (DataType::Int32, Arc::new(array))
+        }
+        ColumnType::Float => {
+            let array: Float64Array = values.iter().map(|value| value.as_f64()).collect();
+            (DataType::Float64, Arc::new(array))
+        }
+        ColumnType::Text => {
+            let array: StringArray = values.iter().map(|value| match value {
+                CellValue::Null => None,
+                value => Some(value.to_string()),
+            }).collect();
+            (DataType::Utf8, Arc::new(array))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Array;
+
+    use super::*;
+
+    #[test]
+    fn test_to_arrow() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let batch = doc.to_arrow().unwrap();
+        assert_eq!(batch.num_rows(), 12);
+        let schema = batch.schema();
+        let types: Vec<_> = schema.fields().iter().map(|field| field.data_type().clone()).collect();
+        assert_eq!(types, vec![DataType::Int32, DataType::Utf8, DataType::Float64, DataType::Float64, DataType::Utf8]);
+        assert!(!schema.field(0).is_nullable());
+        assert!(schema.field(3).is_nullable());
+
+        let some_null = batch.column(3).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(some_null.value(2), 64.);
+        assert!(some_null.is_null(3));
+        assert_eq!(batch.column(4).null_count(), 12);
+    }
+
+    #[test]
+    fn test_to_arrow_null_symbols() {
+        // Invalid UTF-8 symbols decode as null, so the column must be nullable
+        let doc = QvdDocument::read("tests/invalid_utf8.qvd").unwrap();
+        let batch = doc.to_arrow().unwrap();
+        assert!(batch.schema().field(1).is_nullable());
+        assert_eq!(batch.column(1).null_count(), doc.columns()[1].null_count());
+        assert_eq!(batch.column(1).null_count(), 6);
+    }
+
+    #[test]
+    fn test_write_arrow_ipc() {
+        use arrow_ipc::reader::StreamReader;
+
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut buf = Vec::new();
+        doc.write_arrow_ipc(&mut buf).unwrap();
+
+        let reader = StreamReader::try_new(buf.as_slice(), None).unwrap();
+        let batches: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), doc.row_count());
+        assert_eq!(batches[0], doc.to_arrow().unwrap());
+    }
+}
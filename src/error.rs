@@ -6,10 +6,51 @@ pub struct QvdError {
     message: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum QvdErrorKind {
     ReadFile,
-    Utf8Error
+    WriteFile,
+    Utf8Error,
+    SchemaMismatch,
+    ColumnNotFound { column: String },
+    DuplicateColumn { column: String },
+    NotNumeric,
+    /// The file doesn't match the layout its header declares.
+    InvalidFormat,
+    InvalidValue,
+    /// The XML header could not be parsed.
+    InvalidHeader,
+    /// The file ends before the symbol tables or records its header declares.
+    Truncated,
+    /// The symbols of a field lie outside the symbol tables.
+    FieldOutOfBounds { field: String },
+    /// The symbols of two fields share bytes, so the header is corrupt.
+    OverlappingFields { field: String, other: String },
+    /// A field's bit offset and width don't fit in a record.
+    InvalidBitLayout { field: String },
+    /// A symbol at byte `offset` of its field's symbol table is truncated or
+    /// has an unknown type.
+    InvalidSymbol { offset: usize },
+    /// A text symbol isn't valid UTF-8, with `QvdReadOptions::reject_invalid_utf8`.
+    /// `offset` is the first invalid byte within the column's symbol table.
+    InvalidUtf8 { column: String, symbol_index: usize, offset: usize },
+    /// A `QvdDocument::query` expression is malformed at byte `position`.
+    InvalidExpression { position: usize },
+}
+
+impl QvdError {
+    pub(crate) fn new(kind: QvdErrorKind, message: impl Into<String>) -> Self {
+        QvdError { kind, message: message.into() }
+    }
+
+    pub fn kind(&self) -> &QvdErrorKind {
+        &self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl From<io::Error> for QvdError {
@@ -23,3 +64,11 @@ impl From<Utf8Error> for QvdError {
         QvdError { kind: QvdErrorKind::Utf8Error, message: value.to_string() }
     }
 }
+
+impl std::fmt::Display for QvdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for QvdError {}
@@ -0,0 +1,316 @@
+use std::{io::{self, Write}, path::Path};
+
+use crate::{error::{QvdError, QvdErrorKind}, types::{CellValue, QvdDocument}};
+
+/// Controls how cell values are rendered by the CSV and JSON exporters.
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions<'a> {
+    /// Written for `CellValue::Null` in CSV output, e.g. `\N` for Postgres `COPY`.
+    /// JSON output always uses `null`.
+    pub null_token: &'a str,
+    /// How `CellValue::Float` is rendered in CSV and JSON output.
+    pub float_format: FloatFormat,
+}
+
+/// Rendering policy for floating point values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// `f64`'s `Display` formatting, which writes every digit out without an
+    /// exponent, so `10.0` renders as `10` and `1e300` as 301 digits. Used
+    /// by `Display`.
+    #[default]
+    Shortest,
+    /// A fixed number of decimals, so `10.0` renders as `10.0` with `Fixed(1)`.
+    Fixed(usize),
+}
+
+impl FloatFormat {
+    pub fn format(&self, f: f64) -> String {
+        match *self {
+            FloatFormat::Shortest => f.to_string(),
+            FloatFormat::Fixed(decimals) => format!("{f:.decimals$}"),
+        }
+    }
+}
+
+impl QvdDocument {
+    /// Writes the document as CSV with a header row of column names.
+    pub fn write_csv<W: Write>(&self, mut writer: W, options: &ExportOptions) -> Result<(), QvdError> {
+        write_csv_header(self, &mut writer)
+            .and_then(|_| write_csv_rows(self, &mut writer, options))
+            .map_err(write_error)
+    }
+
+    /// Writes only the CSV header row of column names.
+    pub fn write_csv_header<W: Write>(&self, writer: W) -> Result<(), QvdError> {
+        write_csv_header(self, writer).map_err(write_error)
+    }
+
+    /// Writes the CSV rows without a header, e.g. to append several
+    /// documents with the same columns below one `write_csv_header`.
+    pub fn write_csv_rows_only<W: Write>(&self, writer: W, options: &ExportOptions) -> Result<(), QvdError> {
+        write_csv_rows(self, writer, options).map_err(write_error)
+    }
+
+    /// Writes the document as a JSON array with one object per row. Object
+    /// keys are written in column order, so the output is reproducible.
+    pub fn write_json<W: Write>(&self, writer: W, options: &ExportOptions) -> Result<(), QvdError> {
+        write_json(self, writer, options).map_err(write_error)
+    }
+
+    /// Writes the document as a Parquet file of one row group, Snappy
+    /// compressed. Column types follow `to_arrow`.
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet<W: Write + Send>(&self, writer: W) -> Result<(), QvdError> {
+        use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+        let batch = self.to_arrow()?;
+        let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let parquet_error = |err: parquet::errors::ParquetError| QvdError::new(QvdErrorKind::WriteFile, err.to_string());
+        let mut writer = ArrowWriter::try_new(writer, batch.schema(), Some(properties)).map_err(parquet_error)?;
+        writer.write(&batch).map_err(parquet_error)?;
+        writer.close().map_err(parquet_error)?;
+        Ok(())
+    }
+
+    /// Reads a file and renders its table name, size and first `rows` rows as
+    /// a plain text table.
+    pub fn preview(path: impl AsRef<Path>, rows: usize) -> Result<String, QvdError> {
+        let doc = QvdDocument::read(path)?;
+        Ok(format_table(&doc, rows))
+    }
+}
+
+fn write_error(err: io::Error) -> QvdError {
+    QvdError::new(QvdErrorKind::WriteFile, err.to_string())
+}
+
+fn write_csv_header<W: Write>(doc: &QvdDocument, mut writer: W) -> io::Result<()> {
+    let header: Vec<_> = doc.columns().iter().map(|col| csv_field(&col.header.0)).collect();
+    writeln!(writer, "{}", header.join(","))?;
+    writer.flush()
+}
+
+fn write_csv_rows<W: Write>(doc: &QvdDocument, mut writer: W, options: &ExportOptions) -> io::Result<()> {
+    for row in doc.rows() {
+        let line: Vec<_> = row.iter().map(|cell| match cell {
+            CellValue::Null => options.null_token.to_string(),
+            CellValue::Text(s) | CellValue::Dual(_, s) => csv_field(s),
+            CellValue::Float(f) => options.float_format.format(*f),
+            cell => cell.to_string(),
+        }).collect();
+        writeln!(writer, "{}", line.join(","))?;
+    }
+    writer.flush()
+}
+
+// Quote a field only if it contains a delimiter, quote or line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn write_json<W: Write>(doc: &QvdDocument, mut writer: W, options: &ExportOptions) -> io::Result<()> {
+    let keys: Vec<_> = doc.columns().iter().map(|col| json_string(&col.header.0)).collect();
+    write!(writer, "[")?;
+    for (row_idx, row) in doc.rows().enumerate() {
+        if row_idx > 0 {
+            write!(writer, ",")?;
+        }
+        write!(writer, "{{")?;
+        for (col_idx, (key, cell)) in keys.iter().zip(row).enumerate() {
+            if col_idx > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{key}:{}", json_value(cell, options.float_format))?;
+        }
+        write!(writer, "}}")?;
+    }
+    writeln!(writer, "]")?;
+    writer.flush()
+}
+
+fn json_value(cell: &CellValue, float_format: FloatFormat) -> String {
+    match cell {
+        CellValue::Text(s) | CellValue::Dual(_, s) => json_string(s),
+        CellValue::Int(i) => i.to_string(),
+        // JSON has no representation for NaN or infinity
+        CellValue::Float(f) if f.is_finite() => float_format.format(*f),
+        CellValue::Float(_) | CellValue::Null => "null".to_string(),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn format_table(doc: &QvdDocument, rows: usize) -> String {
+    let header: Vec<_> = doc.columns().iter().map(|col| col.header.0.clone()).collect();
+    let cells: Vec<Vec<_>> = doc.rows()
+        .take(rows)
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+    let widths: Vec<_> = header.iter().enumerate().map(|(i, name)| {
+        cells.iter().map(|row| row[i].chars().count()).fold(name.chars().count(), usize::max)
+    }).collect();
+    let line = |values: &[String]| {
+        let padded: Vec<_> = values.iter().zip(&widths).map(|(v, &w)| format!("{v:<w$}")).collect();
+        format!("| {} |\n", padded.join(" | "))
+    };
+
+    let metadata = doc.metadata();
+    let mut out = format!("{}: {} rows, {} columns\n", metadata.table_name(), doc.row_count(), header.len());
+    out.push_str(&line(&header));
+    let separator: Vec<_> = widths.iter().map(|&w| "-".repeat(w)).collect();
+    out.push_str(&line(&separator));
+    for row in &cells {
+        out.push_str(&line(row));
+    }
+    if doc.row_count() > rows {
+        out.push_str(&format!("... {} more rows\n", doc.row_count() - rows));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::QvdWriter;
+
+    use super::*;
+
+    #[test]
+    fn test_write_csv_null_token() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut buf = Vec::new();
+        doc.write_csv(&mut buf, &ExportOptions { null_token: "\\N", ..Default::default() }).unwrap();
+        let csv = String::from_utf8(buf).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[0], "all_int,all_string,all_float,some_null,all Null");
+        assert_eq!(lines[1], "1,Q1,1.1,1.2,\\N");
+        assert_eq!(lines[4], "4,Q2,4.4,\\N,\\N");
+    }
+
+    #[test]
+    fn test_append_csv_rows() {
+        let first = QvdDocument::read("tests/partitioned/ints_1.qvd").unwrap();
+        let second = QvdDocument::read("tests/partitioned/ints_2.qvd").unwrap();
+        let mut buf = Vec::new();
+        first.write_csv_header(&mut buf).unwrap();
+        for doc in [&first, &second] {
+            doc.write_csv_rows_only(&mut buf, &ExportOptions::default()).unwrap();
+        }
+        assert_eq!(String::from_utf8(buf).unwrap(), "RowNo()\n1\n2\n3\n4\n5\n6\n");
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_preview() {
+        let preview = QvdDocument::preview("tests/test_file.qvd", 3).unwrap();
+        for name in ["all_int", "all_string", "all_float", "some_null", "all Null"] {
+            assert!(preview.contains(name));
+        }
+        let lines: Vec<_> = preview.lines().collect();
+        assert_eq!(lines[0], "TempData2: 12 rows, 5 columns");
+        assert_eq!(lines[1], "| all_int | all_string | all_float | some_null | all Null |");
+        assert_eq!(lines[3], "| 1       | Q1         | 1.1       | 1.2       |          |");
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[6], "... 9 more rows");
+    }
+
+    #[test]
+    fn test_write_json_key_order() {
+        let mut writer = QvdWriter::new("order", &["zeta", "alpha", "mid"]);
+        writer.push_row(&[1.into(), 2.into(), 3.into()]).unwrap();
+        writer.push_row(&[4.into(), 5.into(), 6.into()]).unwrap();
+        let mut qvd = Vec::new();
+        writer.finish(&mut qvd).unwrap();
+        let doc = QvdDocument::from_bytes(&qvd).unwrap();
+
+        let mut buf = Vec::new();
+        doc.write_json(&mut buf, &ExportOptions::default()).unwrap();
+        let json = String::from_utf8(buf).unwrap();
+        assert_eq!(json.trim_end(), r#"[{"zeta":1,"alpha":2,"mid":3},{"zeta":4,"alpha":5,"mid":6}]"#);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_write_parquet() {
+        use arrow_array::{Array, Float64Array, Int32Array, StringArray};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut buf = Vec::new();
+        doc.write_parquet(&mut buf).unwrap();
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(buf)).unwrap();
+        let compression = builder.metadata().row_group(0).column(0).compression();
+        assert_eq!(compression, parquet::basic::Compression::SNAPPY);
+        let batches: Vec<_> = builder.build().unwrap().collect::<Result<_, _>>().unwrap();
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 12);
+
+        let ints = batch.column(0).as_any().downcast_ref::<Int32Array>().unwrap();
+        let quarters = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+        let some_null = batch.column(3).as_any().downcast_ref::<Float64Array>().unwrap();
+        for (row, values) in doc.rows().enumerate() {
+            assert_eq!(values[0].as_f64(), Some(ints.value(row) as f64));
+            assert_eq!(values[1].to_string(), quarters.value(row));
+            assert_eq!(values[3].as_f64(), some_null.is_valid(row).then(|| some_null.value(row)));
+        }
+        assert_eq!(batch.column(4).null_count(), 12);
+    }
+
+    #[test]
+    fn test_write_json_null() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut buf = Vec::new();
+        doc.write_json(&mut buf, &ExportOptions { null_token: "\\N", ..Default::default() }).unwrap();
+        let json = String::from_utf8(buf).unwrap();
+        assert!(json.starts_with(r#"[{"all_int":1,"all_string":"Q1","all_float":1.1,"some_null":1.2,"all Null":null},"#));
+        assert!(!json.contains("\\N"));
+    }
+
+    #[test]
+    fn test_float_format() {
+        use crate::types::Column;
+
+        let doc = QvdDocument::from_columns(vec![Column::from_values("f", vec![CellValue::Float(10.0)])]);
+        let render = |options: &ExportOptions| {
+            let (mut csv, mut json) = (Vec::new(), Vec::new());
+            doc.write_csv_rows_only(&mut csv, options).unwrap();
+            doc.write_json(&mut json, options).unwrap();
+            (String::from_utf8(csv).unwrap(), String::from_utf8(json).unwrap())
+        };
+
+        assert_eq!(CellValue::Float(10.0).to_string(), "10");
+        assert_eq!(FloatFormat::Shortest.format(1e300), format!("1{}", "0".repeat(300)));
+        assert_eq!(FloatFormat::Shortest.format(1e-300), format!("0.{}1", "0".repeat(299)));
+        assert_eq!(render(&ExportOptions::default()), ("10\n".to_string(), "[{\"f\":10}]\n".to_string()));
+        let fixed = ExportOptions { float_format: FloatFormat::Fixed(1), ..Default::default() };
+        assert_eq!(render(&fixed), ("10.0\n".to_string(), "[{\"f\":10.0}]\n".to_string()));
+    }
+}
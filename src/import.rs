@@ -0,0 +1,146 @@
+use std::io::{BufRead, BufReader, Read};
+
+use crate::{error::{QvdError, QvdErrorKind}, reader::parse_number, types::{CellValue, Column, CellValueRef, QvdDocument}};
+
+/// Controls how CSV input is parsed by `QvdDocument::from_csv_reader`.
+#[derive(Debug, Clone)]
+pub struct CsvReadOptions<'a> {
+    pub delimiter: char,
+    /// Fields equal to this are read as `CellValue::Null`. Empty by default,
+    /// so empty fields are nulls.
+    pub null_token: &'a str,
+}
+
+impl Default for CsvReadOptions<'_> {
+    fn default() -> Self {
+        Self { delimiter: ',', null_token: "" }
+    }
+}
+
+impl QvdDocument {
+    /// Builds a document from CSV with a header row. Each column becomes `Int`
+    /// if all its values parse as integers, `Float` if they all parse as
+    /// numbers and `Text` otherwise, using the same rules as string symbols
+    /// in .qvd files. Quoted fields may contain delimiters, quotes (`""`)
+    /// and line breaks.
+    pub fn from_csv_reader<R: Read>(reader: R, options: &CsvReadOptions) -> Result<Self, QvdError> {
+        let mut records = Records { reader: BufReader::new(reader), delimiter: options.delimiter };
+        let header = records.next_record()?
+            .ok_or_else(|| QvdError::new(QvdErrorKind::InvalidFormat, "csv input has no header row"))?;
+        let mut values: Vec<Vec<Option<String>>> = vec![Vec::new(); header.len()];
+        while let Some(record) = records.next_record()? {
+            if record.len() != header.len() {
+                return Err(QvdError::new(
+                    QvdErrorKind::SchemaMismatch,
+                    format!("csv row {} has {} fields, expected {}", values[0].len() + 1, record.len(), header.len()),
+                ));
+            }
+            for (column, field) in values.iter_mut().zip(record) {
+                column.push((field != options.null_token).then_some(field));
+            }
+        }
+        let columns = header.into_iter().zip(values).map(|(name, values)| {
+            Column::from_values(name, infer_values(values))
+        }).collect();
+        Ok(QvdDocument::from_columns(columns))
+    }
+}
+
+fn infer_values(values: Vec<Option<String>>) -> Vec<CellValue> {
+    let numbers: Option<Vec<Option<CellValueRef>>> = values.iter()
+        .map(|value| match value {
+            Some(s) => parse_number(s).map(Some),
+            None => Some(None),
+        })
+        .collect();
+    match numbers {
+        Some(numbers) => {
+            let all_int = numbers.iter().flatten().all(|number| matches!(number, CellValueRef::Int(_)));
+            numbers.into_iter().map(|number| match number {
+                Some(CellValueRef::Int(i)) if all_int => CellValue::Int(i),
+                Some(number) => CellValue::from(number).as_f64().map_or(CellValue::Null, CellValue::Float),
+                None => CellValue::Null,
+            }).collect()
+        }
+        None => values.into_iter().map(CellValue::from).collect(),
+    }
+}
+
+struct Records<R> {
+    reader: R,
+    delimiter: char,
+}
+
+impl<R: BufRead> Records<R> {
+    // Next record split into fields, `None` at the end of the input
+    fn next_record(&mut self) -> Result<Option<Vec<String>>, QvdError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if quoted && chars.peek() == Some(&'"') => {
+                        field.push('"');
+                        chars.next();
+                    }
+                    '"' if quoted => quoted = false,
+                    '"' if field.is_empty() => quoted = true,
+                    c if c == self.delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+                    '\n' | '\r' if !quoted => {}
+                    c => field.push(c),
+                }
+            }
+            if !quoted {
+                break;
+            }
+            // a quoted field continues on the next line
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(QvdError::new(QvdErrorKind::InvalidFormat, "csv input ends inside a quoted field"));
+            }
+        }
+        fields.push(field);
+        Ok(Some(fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_csv_reader() {
+        let csv = "id,name,score\n1,a,1.5\n2,\"b, \"\"c\"\"\nd\",\n3,e,2\n";
+        let doc = QvdDocument::from_csv_reader(csv.as_bytes(), &CsvReadOptions::default()).unwrap();
+        assert_eq!(doc.row_count(), 3);
+        assert_eq!(doc.column_names(), vec!["id", "name", "score"]);
+        let types: Vec<_> = doc.columns().iter()
+            .map(|col| col.as_values().iter().map(|value| value.type_name()).collect::<Vec<_>>())
+            .collect();
+        assert_eq!(types, vec![
+            vec!["int", "int", "int"],
+            vec!["text", "text", "text"],
+            vec!["float", "null", "float"],
+        ]);
+        assert_eq!(doc.column("name").unwrap().as_values()[1], &CellValue::from("b, \"c\"\nd"));
+
+        let mut qvd = Vec::new();
+        doc.write(&mut qvd).unwrap();
+        let read_back = QvdDocument::from_bytes(&qvd).unwrap();
+        assert_eq!(read_back.rows_owned().collect::<Vec<_>>(), doc.rows_owned().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_csv_row_length_mismatch() {
+        let err = QvdDocument::from_csv_reader("a,b\n1\n".as_bytes(), &CsvReadOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::SchemaMismatch);
+        let err = QvdDocument::from_csv_reader("a\n\"1\n".as_bytes(), &CsvReadOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::InvalidFormat);
+    }
+}
@@ -4,14 +4,36 @@ pub(crate) mod qvd_structure;
 pub mod types;
 pub(crate) mod reader;
 pub mod error;
+pub mod export;
+pub mod import;
+mod query;
+pub mod writer;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+#[cfg(feature = "arrow")]
+mod arrow;
 
-pub use types::{QvdDocument, Header, Column, CellValue};
+/// The parsed XML header of a .qvd file, for building documents with
+/// `QvdDocument::from_parts`.
+pub mod raw {
+    pub use crate::qvd_structure::{Fields, QvdFieldHeader, QvdTableHeader};
+}
+
+pub use types::{QvdDocument, QvdDocumentBuilder, QvdMetadata, Header, Column, CellValue, ColumnType, ColumnStats, DocumentDiff, FromRow, QvdTable, SchemaDifference, QvdDocumentRef, ColumnRef, CellValueRef};
+pub use export::{ExportOptions, FloatFormat};
+pub use import::CsvReadOptions;
+pub use writer::QvdWriter;
+pub use reader::{QvdReadOptions, QvdReader, Utf8Problem};
+#[cfg(feature = "mmap")]
+pub use mapped::MappedQvdDocument;
 
     
 
 #[cfg(all(RUSTC_IS_NIGHTLY, test))]
 mod tests {
-    use crate::QvdDocument;
+    use crate::{QvdDocument, QvdReader};
 
     extern crate test;
 
@@ -38,6 +60,35 @@ mod tests {
         })
     }
 
+    #[bench]
+    fn read_test_file_to_par_rows(b: &mut test::Bencher) {
+        use rayon::iter::ParallelIterator;
+
+        let qvd = QvdDocument::read("tests/big_file.qvd").unwrap();
+        b.iter(|| {
+            qvd.par_rows().map(|row| row.len()).sum::<usize>()
+        })
+    }
+
+    #[bench]
+    fn read_small_files(b: &mut test::Bencher) {
+        b.iter(|| {
+            for _ in 0..100 {
+                QvdDocument::read("tests/test_file.qvd").unwrap();
+            }
+        })
+    }
+
+    #[bench]
+    fn read_small_files_with_reader(b: &mut test::Bencher) {
+        let mut reader = QvdReader::new();
+        b.iter(|| {
+            for _ in 0..100 {
+                reader.read_into("tests/test_file.qvd").unwrap();
+            }
+        })
+    }
+
     #[bench]
     fn read_test_file_to_row_iter_alt(b: &mut test::Bencher) {        
         let qvd = QvdDocument::read("tests/big_file.qvd").unwrap();
@@ -0,0 +1,208 @@
+use std::{fs::File, ops::Range, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{
+    error::QvdError,
+    qvd_structure::QvdFieldHeader,
+    reader::{check_bit_layout, decode_symbol, metadata, read_header, record_index, records, split_body, Field, QvdReadOptions},
+    types::{column_not_found, CellValue, CellValueRef, Header, QvdMetadata},
+};
+
+/// A .qvd file kept memory mapped, values are decoded from the mapped bytes
+/// when they are accessed instead of all at once. Only the position of each
+/// symbol is kept in memory, which suits random access to a few rows of a
+/// large file.
+///
+/// The file must not be modified while it is mapped.
+pub struct MappedQvdDocument {
+    mmap: Mmap,
+    metadata: QvdMetadata,
+    fields: Vec<MappedField>,
+    rows_start: usize,
+}
+
+struct MappedField {
+    header: QvdFieldHeader,
+    // symbol table of the field within the mapped file
+    symbols: Range<usize>,
+    // start of each symbol within `symbols`
+    symbol_positions: Vec<usize>,
+}
+
+impl MappedQvdDocument {
+    /// Maps the file and checks its layout, the symbol tables are scanned once
+    /// to find where each symbol starts.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, QvdError> {
+        Self::open_fields(path, None)
+    }
+
+    /// Like `open`, but only maps the given columns, in the order given. The
+    /// symbol tables of other columns are never touched, so their pages are
+    /// not read from disk.
+    pub fn open_columns(path: impl AsRef<Path>, columns: &[&str]) -> Result<Self, QvdError> {
+        Self::open_fields(path, Some(columns))
+    }
+
+    fn open_fields(path: impl AsRef<Path>, columns: Option<&[&str]>) -> Result<Self, QvdError> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read only, modifying the file while it is mapped is documented as unsupported
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let mut body = &mmap[..];
+        let qvd_structure = read_header(&mut body, &mut Vec::new())?;
+        let body_start = mmap.len() - body.len();
+        let (symbol_map, row_section) = split_body(&qvd_structure, body)?;
+        let record_byte_size = qvd_structure.record_byte_size;
+        records(row_section, record_byte_size, qvd_structure.no_of_records)?;
+
+        let headers: Vec<&QvdFieldHeader> = match columns {
+            None => qvd_structure.fields.headers.iter().collect(),
+            Some(columns) => columns.iter().map(|&name| {
+                qvd_structure.fields.headers.iter()
+                    .find(|header| header.field_name == name)
+                    .ok_or_else(|| column_not_found(name))
+            }).collect::<Result<_, QvdError>>()?,
+        };
+        let options = QvdReadOptions::default();
+        let fields = headers.into_iter().map(|header| {
+            if record_byte_size > 0 {
+                check_bit_layout(header, record_byte_size)?;
+            }
+            let field_buf = Field::from_header_and_symbol_map(header, symbol_map)?.field_buf;
+            let mut symbol_positions = Vec::new();
+            let mut pos = 0;
+            while pos < field_buf.len() {
+                symbol_positions.push(pos);
+                pos = decode_symbol(field_buf, pos, &options)?.1;
+            }
+            let start = body_start + header.offset;
+            Ok(MappedField {
+                header: header.clone(),
+                symbols: start..start + header.length,
+                symbol_positions,
+            })
+        }).collect::<Result<_, QvdError>>()?;
+
+        Ok(Self {
+            metadata: metadata(&qvd_structure, row_section.len()),
+            fields,
+            rows_start: body_start + qvd_structure.offset,
+            mmap,
+        })
+    }
+
+    pub fn metadata(&self) -> &QvdMetadata {
+        &self.metadata
+    }
+
+    pub fn headers(&self) -> Vec<Header> {
+        self.fields.iter().map(|field| Header(field.header.field_name.clone())).collect()
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.metadata.no_of_records
+    }
+
+    /// Values of row `row` in column order, `None` past the last row.
+    pub fn row(&self, row: usize) -> Option<Vec<CellValueRef<'_>>> {
+        (row < self.row_count()).then(|| {
+            self.fields.iter().map(|field| self.value_at(field, row)).collect()
+        })
+    }
+
+    /// Value of column `column` in row `row`.
+    pub fn value(&self, row: usize, column: &str) -> Option<CellValueRef<'_>> {
+        let field = self.field(column)?;
+        (row < self.row_count()).then(|| self.value_at(field, row))
+    }
+
+    /// Row indexes holding `value` in column `column_name`, matching like
+    /// `Column::find_row_indexes`.
+    pub fn find_row_indexes(&self, column_name: &str, value: impl Into<CellValue>) -> Vec<usize> {
+        let Some(field) = self.field(column_name) else {
+            return Vec::new();
+        };
+        let value = value.into();
+        let matching: Vec<bool> = (0..field.symbol_positions.len())
+            .map(|i| CellValue::from(self.symbol(field, i as isize)).same_value(&value))
+            .collect();
+        (0..self.row_count())
+            .filter(|&row| {
+                let idx = self.symbol_index(field, row);
+                usize::try_from(idx).ok().and_then(|i| matching.get(i)).copied().unwrap_or(false)
+            })
+            .collect()
+    }
+
+    fn field(&self, name: &str) -> Option<&MappedField> {
+        self.fields.iter().find(|field| field.header.field_name == name)
+    }
+
+    fn value_at(&self, field: &MappedField, row: usize) -> CellValueRef<'_> {
+        self.symbol(field, self.symbol_index(field, row))
+    }
+
+    fn symbol_index(&self, field: &MappedField, row: usize) -> isize {
+        let record_byte_size = self.metadata.record_byte_size;
+        if record_byte_size == 0 {
+            return field.header.bias;
+        }
+        let start = self.rows_start + row * record_byte_size;
+        record_index(&self.mmap[start..start + record_byte_size], &field.header)
+    }
+
+    // Symbols were decoded once in `open`, so decoding them again can't fail
+    fn symbol(&self, field: &MappedField, index: isize) -> CellValueRef<'_> {
+        let field_buf = &self.mmap[field.symbols.clone()];
+        usize::try_from(index).ok()
+            .and_then(|i| field.symbol_positions.get(i))
+            .and_then(|&pos| decode_symbol(field_buf, pos, &QvdReadOptions::default()).ok())
+            .map_or(CellValueRef::Null, |(value, _)| value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::QvdDocument;
+
+    use super::*;
+
+    #[test]
+    fn test_random_row_access() {
+        for path in ["tests/test_file.qvd", "tests/big_file.qvd"] {
+            let mapped = MappedQvdDocument::open(path).unwrap();
+            let doc = QvdDocument::read(path).unwrap();
+            assert_eq!(mapped.row_count(), doc.row_count());
+            let rows = doc.row_count();
+            for row in [rows - 1, 0, rows / 2, 7, 3, rows / 3] {
+                let expected: Vec<_> = doc.rows().nth(row).unwrap().into_iter().cloned().collect();
+                let values: Vec<_> = mapped.row(row).unwrap().into_iter().map(CellValue::from).collect();
+                assert_eq!(values, expected);
+            }
+            assert!(mapped.row(rows).is_none());
+        }
+    }
+
+    #[test]
+    fn test_open_columns() {
+        let mapped = MappedQvdDocument::open_columns("tests/test_file.qvd", &["some_null", "all_string"]).unwrap();
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(mapped.headers(), vec![Header("some_null".into()), Header("all_string".into())]);
+        let values: Vec<_> = mapped.row(3).unwrap().into_iter().map(CellValue::from).collect();
+        let row = doc.rows().nth(3).unwrap();
+        assert_eq!(values, vec![row[3].clone(), row[1].clone()]);
+        assert!(MappedQvdDocument::open_columns("tests/test_file.qvd", &["missing"]).is_err());
+    }
+
+    #[test]
+    fn test_query_mapped_file() {
+        let mapped = MappedQvdDocument::open("tests/test_file.qvd").unwrap();
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(mapped.value(0, "all_string"), Some(CellValueRef::Text("Q1")));
+        assert_eq!(mapped.value(3, "some_null"), Some(CellValueRef::Null));
+        assert_eq!(mapped.value(0, "missing"), None);
+        assert_eq!(mapped.find_row_indexes("all_string", "Q1"), doc.find_row_indexes("all_string", "Q1"));
+        assert_eq!(mapped.headers(), doc.columns().iter().map(|col| col.header()).collect::<Vec<_>>());
+    }
+}
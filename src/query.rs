@@ -0,0 +1,329 @@
+use std::cmp::Ordering;
+
+use crate::{error::{QvdError, QvdErrorKind}, reader::parse_number, types::{CellValue, Column, QvdDocument}};
+
+impl QvdDocument {
+    /// Indexes of the rows matching a filter expression such as
+    /// `amount > 1000 AND quarter = 'Q1'`.
+    ///
+    /// Comparisons (`=`, `!=` or `<>`, `<`, `<=`, `>`, `>=`) take columns and
+    /// literals on either side and are combined with `AND` and `OR`, `AND`
+    /// binding tighter, and grouped with parentheses. Text literals are single
+    /// quoted, with `''` for a quote; column names that aren't plain
+    /// identifiers are written as `[all Null]` or `"all Null"`. Numbers
+    /// compare numerically and text by string, a `Dual` as either. Null never
+    /// matches, and values of different kinds are only unequal.
+    pub fn query(&self, expr: &str) -> Result<Vec<usize>, QvdError> {
+        let expr = Parser { input: expr, tokens: tokenize(expr)?, pos: 0, depth: 0 }.parse()?;
+        let mask = self.evaluate(&expr)?;
+        Ok(mask.into_iter().enumerate().filter(|&(_, matches)| matches).map(|(row, _)| row).collect())
+    }
+
+    // Whether each row matches `expr`
+    fn evaluate(&self, expr: &Expr) -> Result<Vec<bool>, QvdError> {
+        Ok(match expr {
+            Expr::And(terms) => {
+                let mut mask = vec![true; self.row_count()];
+                for term in terms {
+                    mask.iter_mut().zip(self.evaluate(term)?).for_each(|(m, t)| *m &= t);
+                }
+                mask
+            }
+            Expr::Or(terms) => {
+                let mut mask = vec![false; self.row_count()];
+                for term in terms {
+                    mask.iter_mut().zip(self.evaluate(term)?).for_each(|(m, t)| *m |= t);
+                }
+                mask
+            }
+            Expr::Compare(left, op, right) => match (left, right) {
+                (Operand::Column(name), Operand::Value(value)) => {
+                    symbol_mask(self.column_or_err(name)?, |symbol| op.test(symbol, value))
+                }
+                (Operand::Value(value), Operand::Column(name)) => {
+                    symbol_mask(self.column_or_err(name)?, |symbol| op.test(value, symbol))
+                }
+                (Operand::Column(left), Operand::Column(right)) => {
+                    let (left, right) = (self.column_or_err(left)?, self.column_or_err(right)?);
+                    (0..self.row_count()).map(|row| op.test(left.resolve_row(row), right.resolve_row(row))).collect()
+                }
+                (Operand::Value(left), Operand::Value(right)) => vec![op.test(left, right); self.row_count()],
+            },
+        })
+    }
+}
+
+// Tests every symbol once, then looks rows up by their index
+fn symbol_mask(col: &Column, test: impl Fn(&CellValue) -> bool) -> Vec<bool> {
+    let symbols: Vec<bool> = col.symbols.iter().map(&test).collect();
+    let null = test(&CellValue::Null);
+    col.indexes.iter()
+        .map(|&idx| usize::try_from(idx).ok().and_then(|i| symbols.get(i)).copied().unwrap_or(null))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn test(self, left: &CellValue, right: &CellValue) -> bool {
+        if *left == CellValue::Null || *right == CellValue::Null {
+            return false;
+        }
+        let ordering = compare(left, right);
+        match self {
+            CompareOp::Eq => ordering == Some(Ordering::Equal),
+            CompareOp::Ne => ordering != Some(Ordering::Equal),
+            CompareOp::Lt => ordering == Some(Ordering::Less),
+            CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            CompareOp::Gt => ordering == Some(Ordering::Greater),
+            CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+// Text against text or a dual's text, otherwise numerically. `None` if the
+// values can't be ordered.
+fn compare(left: &CellValue, right: &CellValue) -> Option<Ordering> {
+    match (left, right) {
+        (CellValue::Text(l) | CellValue::Dual(_, l), CellValue::Text(r)) |
+        (CellValue::Text(l), CellValue::Dual(_, r)) => Some(l.cmp(r)),
+        _ => left.as_f64()?.partial_cmp(&right.as_f64()?),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Compare(Operand, CompareOp, Operand),
+}
+
+#[derive(Debug, PartialEq)]
+enum Operand {
+    Column(String),
+    Value(CellValue),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Value(CellValue),
+    Op(CompareOp),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+fn invalid(position: usize, message: &str) -> QvdError {
+    QvdError::new(QvdErrorKind::InvalidExpression { position }, format!("{message} at byte {position}"))
+}
+
+// Token with the byte range it was read from
+fn tokenize(input: &str) -> Result<Vec<(usize, usize, Token)>, QvdError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' => Token::Op(CompareOp::Eq),
+            '!' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::Op(CompareOp::Ne),
+            '<' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::Op(CompareOp::Le),
+            '<' if chars.next_if(|&(_, c)| c == '>').is_some() => Token::Op(CompareOp::Ne),
+            '<' => Token::Op(CompareOp::Lt),
+            '>' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::Op(CompareOp::Ge),
+            '>' => Token::Op(CompareOp::Gt),
+            '\'' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) if chars.next_if(|&(_, c)| c == '\'').is_some() => text.push('\''),
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => text.push(c),
+                        None => return Err(invalid(start, "unterminated text literal")),
+                    }
+                }
+                Token::Value(CellValue::Text(text))
+            }
+            '"' | '[' => {
+                let close = if c == '"' { '"' } else { ']' };
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, c)) if c == close => break,
+                        Some((_, c)) => name.push(c),
+                        None => return Err(invalid(start, "unterminated column name")),
+                    }
+                }
+                Token::Name(name)
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut end = start + c.len_utf8();
+                let mut prev = c;
+                while let Some((i, c)) = chars.next_if(|&(_, c)| {
+                    c.is_ascii_alphanumeric() || c == '.' || (matches!(c, '+' | '-') && matches!(prev, 'e' | 'E'))
+                }) {
+                    end = i + c.len_utf8();
+                    prev = c;
+                }
+                let number = parse_number(&input[start..end]).ok_or_else(|| invalid(start, "invalid number"))?;
+                Token::Value(number.into())
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_') {
+                    end = i + c.len_utf8();
+                }
+                match &input[start..end] {
+                    word if word.eq_ignore_ascii_case("and") => Token::And,
+                    word if word.eq_ignore_ascii_case("or") => Token::Or,
+                    word => Token::Name(word.to_string()),
+                }
+            }
+            _ => return Err(invalid(start, &format!("unexpected '{c}'"))),
+        };
+        let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+        tokens.push((start, end, token));
+    }
+    Ok(tokens)
+}
+
+// Recursive descent over `or := and (OR and)*`, `and := primary (AND primary)*`
+// and `primary := '(' or ')' | operand op operand`
+// Deepest nesting of parentheses accepted, so a hostile expression can't
+// overflow the stack while it is parsed or evaluated
+const MAX_DEPTH: usize = 256;
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(usize, usize, Token)>,
+    pos: usize,
+    // Parentheses currently open
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn parse(mut self) -> Result<Expr, QvdError> {
+        let expr = self.or()?;
+        match self.tokens.get(self.pos) {
+            Some(&(start, end, _)) => Err(invalid(start, &format!("unexpected '{}'", &self.input[start..end]))),
+            None => Ok(expr),
+        }
+    }
+
+    // Chains of the same operator are kept flat rather than nested
+    fn or(&mut self) -> Result<Expr, QvdError> {
+        let mut terms = vec![self.and()?];
+        while self.next_if(&Token::Or) {
+            terms.push(self.and()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Expr::Or(terms) })
+    }
+
+    fn and(&mut self) -> Result<Expr, QvdError> {
+        let mut terms = vec![self.primary()?];
+        while self.next_if(&Token::And) {
+            terms.push(self.primary()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Expr::And(terms) })
+    }
+
+    fn primary(&mut self) -> Result<Expr, QvdError> {
+        let start = self.position();
+        if self.next_if(&Token::Open) {
+            if self.depth == MAX_DEPTH {
+                return Err(invalid(start, "expression nested too deeply"));
+            }
+            self.depth += 1;
+            let expr = self.or()?;
+            if !self.next_if(&Token::Close) {
+                return Err(invalid(self.position(), "expected ')'"));
+            }
+            self.depth -= 1;
+            return Ok(expr);
+        }
+        let left = self.operand()?;
+        let op = match self.tokens.get(self.pos) {
+            Some((_, _, Token::Op(op))) => *op,
+            _ => return Err(invalid(self.position(), "expected a comparison operator")),
+        };
+        self.pos += 1;
+        Ok(Expr::Compare(left, op, self.operand()?))
+    }
+
+    fn operand(&mut self) -> Result<Operand, QvdError> {
+        let operand = match self.tokens.get_mut(self.pos) {
+            Some((_, _, Token::Name(name))) => Operand::Column(std::mem::take(name)),
+            Some((_, _, Token::Value(value))) => Operand::Value(std::mem::replace(value, CellValue::Null)),
+            _ => return Err(invalid(self.position(), "expected a column or value")),
+        };
+        self.pos += 1;
+        Ok(operand)
+    }
+
+    fn next_if(&mut self, token: &Token) -> bool {
+        let matches = self.tokens.get(self.pos).is_some_and(|(_, _, t)| t == token);
+        if matches {
+            self.pos += 1;
+        }
+        matches
+    }
+
+    // Start of the current token, or the end of the input
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.input.len(), |&(start, _, _)| start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(doc.query("all_float > 5 AND all_string = 'Q2'").unwrap(), vec![4, 5]);
+        assert_eq!(doc.query("all_string = 'Q1' or all_int >= 11").unwrap(), vec![0, 1, 2, 10, 11]);
+        assert_eq!(
+            doc.query("(all_string = 'Q1' OR all_string = 'Q4') AND some_null > 2").unwrap(),
+            vec![1, 2, 9, 10, 11],
+        );
+        assert_eq!(doc.query("3 < all_int AND all_int <= 4").unwrap(), vec![3]);
+        assert_eq!(doc.query("all_float < some_null").unwrap(), vec![0, 1, 2, 7, 11]);
+        assert_eq!(doc.query("some_null <> 1").unwrap(), vec![0, 1, 2, 7, 8, 9, 10, 11]);
+        assert_eq!(doc.query("[all Null] = 1 OR \"all Null\" != 1").unwrap(), Vec::<usize>::new());
+        assert_eq!(doc.query("all_string != 1 AND all_int < 1.5e0").unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let position = |expr: &str| match doc.query(expr).unwrap_err().kind() {
+            QvdErrorKind::InvalidExpression { position } => *position,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(position("all_int >"), 9);
+        assert_eq!(position("all_string = 'Q1"), 13);
+        assert_eq!(position("(all_int = 1"), 12);
+        assert_eq!(position("all_int = 1 all_int"), 12);
+        assert_eq!(position("all_int ~ 1"), 8);
+        assert!(matches!(doc.query("missing = 1").unwrap_err().kind(), QvdErrorKind::ColumnNotFound { .. }));
+
+        let nested = |depth| format!("{}all_int = 1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(doc.query(&nested(256)).unwrap(), vec![0]);
+        assert_eq!(position(&nested(257)), 256);
+        assert_eq!(position(&nested(100_000)), 256);
+        let chain = vec!["all_int = 1"; 100_000].join(" AND ");
+        assert_eq!(doc.query(&chain).unwrap(), vec![0]);
+    }
+}
@@ -1,40 +1,90 @@
-use serde::Deserialize;
-#[derive(Debug, Deserialize)]
+use std::{fmt, marker::PhantomData, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer};
+#[derive(Debug, Clone, Deserialize)]
 pub struct QvdTableHeader {
-    #[serde(rename = "TableName")]
+    #[serde(rename = "TableName", default)]
     pub table_name: String,
-    #[serde(rename = "CreatorDoc")]
+    #[serde(rename = "CreatorDoc", default)]
     pub creator_doc: String,
     #[serde(rename = "Fields")]
     pub fields: Fields,
-    #[serde(rename = "NoOfRecords")]
-    pub no_of_records: u32,
-    #[serde(rename = "RecordByteSize")]
+    #[serde(rename = "NoOfRecords", deserialize_with = "number")]
+    pub no_of_records: usize,
+    #[serde(rename = "RecordByteSize", deserialize_with = "number")]
     pub record_byte_size: usize,
-    #[serde(rename = "Offset")]
+    #[serde(rename = "Offset", deserialize_with = "number")]
     pub offset: usize,
-    #[serde(rename = "Length")]
+    #[serde(rename = "Length", deserialize_with = "number")]
     pub length: usize,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Fields {
     #[serde(rename = "$value", default)]
     pub headers: Vec<QvdFieldHeader>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct QvdFieldHeader {
     #[serde(rename = "FieldName")]
     pub field_name: String,
-    #[serde(rename = "Offset")]
+    #[serde(rename = "Offset", deserialize_with = "number")]
     pub offset: usize,
-    #[serde(rename = "Length")]
+    #[serde(rename = "Length", deserialize_with = "number")]
     pub length: usize,
-    #[serde(rename = "BitOffset")]
+    // A missing bit offset or bias means the field starts at bit 0 / has no bias
+    #[serde(rename = "BitOffset", default, deserialize_with = "number")]
     pub bit_offset: usize,
-    #[serde(rename = "BitWidth")]
+    #[serde(rename = "BitWidth", deserialize_with = "number")]
     pub bit_width: usize,
-    #[serde(rename = "Bias")]
+    #[serde(rename = "Bias", default, deserialize_with = "number")]
     pub bias: isize,
+    /// Tags such as `$key` or `$numeric`, in header order.
+    #[serde(rename = "Tags", default, deserialize_with = "tags")]
+    pub tags: Vec<String>,
+}
+
+// Tags are written as `<Tags><String>$key</String>...</Tags>`
+fn tags<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    struct Tags {
+        #[serde(rename = "String", default)]
+        values: Vec<String>,
+    }
+
+    Ok(Tags::deserialize(deserializer)?.values)
+}
+
+// Numeric elements are written as plain numbers by most versions, but some
+// quote them or pad them with whitespace, e.g. `<Offset>"123"</Offset>`.
+fn number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + TryFrom<u64> + TryFrom<i64>,
+{
+    struct NumberVisitor<T>(PhantomData<T>);
+
+    impl<T: FromStr + TryFrom<u64> + TryFrom<i64>> de::Visitor<'_> for NumberVisitor<T> {
+        type Value = T;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an integer, possibly quoted")
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<T, E> {
+            T::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<T, E> {
+            T::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+            value.trim().trim_matches(['"', '\'']).trim().parse()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+        }
+    }
+
+    deserializer.deserialize_str(NumberVisitor(PhantomData))
 }
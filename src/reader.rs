@@ -1,139 +1,764 @@
-use std::{fs::File, io::{self, BufRead, BufReader, Read}, path::Path};
+use std::{fs::File, io::{self, BufRead, BufReader, Read, Seek, SeekFrom}, path::Path, sync::Arc};
 
 use bitvec::{order::Msb0, slice::BitSlice};
 use quick_xml::de::from_str;
-use rayon::prelude::*;
+use rayon::{prelude::*, ThreadPool};
 
-use crate::{types::{CellValue, Column, Header}, qvd_structure::{QvdFieldHeader, QvdTableHeader}, error::QvdError};
+use crate::{types::{column_not_found, CellValue, CellValueRef, Column, ColumnRef, Header, QvdDocument, QvdMetadata, NULL_INDEX}, qvd_structure::{QvdFieldHeader, QvdTableHeader}, error::{QvdError, QvdErrorKind}};
 
 const MAX_EXACT_F64: u128 = 1 << 53;  // 2^53
 
-pub(crate) fn read_qvd(file_name: impl AsRef<Path>) -> Result<Vec<Column>, QvdError> {
+/// Options for reading .qvd files, `Default` matches `QvdDocument::read`.
+#[derive(Debug, Clone, Default)]
+pub struct QvdReadOptions {
+    /// Fail with `QvdErrorKind::InvalidValue` when a stored double is NaN or
+    /// infinite. By default such values are kept as `CellValue::Float`.
+    pub reject_non_finite_floats: bool,
+    /// Decode stored doubles that are whole numbers within the `i32` range as
+    /// `CellValue::Int`. By default every stored double is a `CellValue::Float`.
+    pub coerce_integral_floats: bool,
+    /// Fail with `QvdErrorKind::InvalidFormat` when bytes follow the declared
+    /// records. By default such padding or footer bytes are ignored.
+    pub reject_trailing_bytes: bool,
+    /// Fail with `QvdErrorKind::InvalidFormat` when a row's index, after
+    /// applying the field's bias, points past the end of the symbol table or
+    /// is negative without being `NULL_INDEX`. By default such rows read as
+    /// `CellValue::Null`, except in fields without any symbols, where only
+    /// `NULL_INDEX` is ever accepted.
+    pub reject_out_of_range_indexes: bool,
+    /// Decode stored 4 byte integers as unsigned, for fields holding counts
+    /// above `i32::MAX`. Values that don't fit an `i32` become an exact
+    /// `CellValue::Float`. By default they are signed, as the format defines.
+    pub unsigned_ints: bool,
+    /// Fail with `QvdErrorKind::InvalidUtf8`, naming the column and symbol,
+    /// when a text symbol isn't valid UTF-8. By default such symbols read as
+    /// `CellValue::Null`.
+    pub reject_invalid_utf8: bool,
+    /// Pool used to decode fields in parallel, `None` uses the global rayon
+    /// pool. A pool with a single thread decodes the fields one at a time.
+    pub thread_pool: Option<Arc<ThreadPool>>,
+}
+
+impl QvdReadOptions {
+    // Runs `f` within the configured pool, parallel iterators in `f` use its threads
+    fn install<T: Send>(&self, f: impl FnOnce() -> T + Send) -> T {
+        match &self.thread_pool {
+            Some(pool) => pool.install(f),
+            None => f(),
+        }
+    }
+}
+
+pub(crate) fn read_qvd(file_name: impl AsRef<Path>, options: &QvdReadOptions) -> Result<(QvdMetadata, Vec<Column>), QvdError> {
     let file = File::open(&file_name)?;
     let mut reader = BufReader::new(file);
-    let xml: String = get_xml_data(&mut reader)?;
-    let qvd_structure: QvdTableHeader = from_str(&xml).unwrap();    
+    read_qvd_from_reader(&mut reader, options)
+}
+
+/// Reads a .qvd file embedded in a larger file, starting `start_offset` bytes in.
+pub(crate) fn read_qvd_at(file_name: impl AsRef<Path>, start_offset: u64, options: &QvdReadOptions) -> Result<(QvdMetadata, Vec<Column>), QvdError> {
+    let mut file = File::open(&file_name)?;
+    file.seek(SeekFrom::Start(start_offset))?;
+    read_qvd_from_reader(&mut BufReader::new(file), options)
+}
+
+pub(crate) fn read_qvd_from_reader<R: BufRead>(reader: &mut R, options: &QvdReadOptions) -> Result<(QvdMetadata, Vec<Column>), QvdError> {
+    read_qvd_buffered(reader, options, &mut Vec::new(), &mut Vec::new())
+}
+
+/// Reads .qvd files one after another, keeping the buffers the header and
+/// data of the previous file were read into, so a service reading many files
+/// doesn't allocate them afresh for every file.
+#[derive(Debug, Default)]
+pub struct QvdReader {
+    options: QvdReadOptions,
+    xml_buf: Vec<u8>,
+    data_buf: Vec<u8>,
+}
+
+impl QvdReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: QvdReadOptions) -> Self {
+        Self { options, ..Self::default() }
+    }
+
+    pub fn read_into(&mut self, file_name: impl AsRef<Path>) -> Result<QvdDocument, QvdError> {
+        let file = File::open(&file_name)?;
+        let mut reader = BufReader::new(file);
+        let (metadata, columns) = read_qvd_buffered(&mut reader, &self.options, &mut self.xml_buf, &mut self.data_buf)?;
+        Ok(QvdDocument::from_metadata(metadata, columns))
+    }
+}
+
+fn read_qvd_buffered<R: BufRead>(reader: &mut R, options: &QvdReadOptions, xml_buf: &mut Vec<u8>, buf: &mut Vec<u8>) -> Result<(QvdMetadata, Vec<Column>), QvdError> {
+    #[cfg(feature = "tracing")]
+    let _read_span = tracing::info_span!("read_qvd").entered();
+
+    let qvd_structure = read_header(reader, xml_buf)?;
+
+    buf.clear();
+    reader.read_to_end(buf)?;
+    read_qvd_parts(&qvd_structure, buf, options)
+}
+
+/// Decodes the data following an already parsed header.
+pub(crate) fn read_qvd_parts(qvd_structure: &QvdTableHeader, data: &[u8], options: &QvdReadOptions) -> Result<(QvdMetadata, Vec<Column>), QvdError> {
+    let columns = decode_fields(qvd_structure, data, options, |field| field.get_column_values(options))?
+        .into_iter()
+        .map(|(header, symbols, indexes)| Column { header, symbols, indexes })
+        .collect();
+
+    Ok((metadata(qvd_structure, data.len() - qvd_structure.offset), columns))
+}
+
+/// A text symbol that isn't valid UTF-8, found by `QvdDocument::check_utf8`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utf8Problem {
+    pub column: String,
+    /// Position of the symbol in the column's symbol table.
+    pub symbol_index: usize,
+    /// Offset in the file of the first invalid byte.
+    pub byte_offset: usize,
+}
+
+/// Every text symbol of a .qvd file held in memory that isn't valid UTF-8.
+pub(crate) fn check_utf8(bytes: &[u8]) -> Result<Vec<Utf8Problem>, QvdError> {
+    let mut body = bytes;
+    let qvd_structure = read_header(&mut body, &mut Vec::new())?;
+    let body_start = bytes.len() - body.len();
+    let (symbol_map, _) = split_body(&qvd_structure, body)?;
+    let mut problems = Vec::new();
+    for header in &qvd_structure.fields.headers {
+        let field_buf = Field::from_header_and_symbol_map(header, symbol_map)?.field_buf;
+        for (symbol_index, offset) in invalid_utf8_symbols(field_buf)? {
+            problems.push(Utf8Problem {
+                column: header.field_name.clone(),
+                symbol_index,
+                byte_offset: body_start + header.offset + offset,
+            });
+        }
+    }
+    Ok(problems)
+}
+
+// Index of every text symbol that isn't valid UTF-8 and the offset of its
+// first invalid byte in `field_buf`
+fn invalid_utf8_symbols(field_buf: &[u8]) -> Result<Vec<(usize, usize)>, QvdError> {
+    let mut invalid = Vec::new();
+    for (symbol_index, pos) in symbol_positions(field_buf)?.into_iter().enumerate() {
+        let start = match field_buf[pos] {
+            4 => pos + 1,
+            5 => pos + 5,
+            6 => pos + 9,
+            _ => continue,
+        };
+        let end = text_end(field_buf, start)?;
+        if let Err(err) = std::str::from_utf8(&field_buf[start..end]) {
+            invalid.push((symbol_index, start + err.valid_up_to()));
+        }
+    }
+    Ok(invalid)
+}
+
+// With `reject_invalid_utf8`, fails on the first text symbol of the field that
+// isn't valid UTF-8
+fn check_field_utf8(header: &QvdFieldHeader, field_buf: &[u8], options: &QvdReadOptions) -> Result<(), QvdError> {
+    if !options.reject_invalid_utf8 {
+        return Ok(());
+    }
+    match invalid_utf8_symbols(field_buf)?.first() {
+        Some(&(symbol_index, offset)) => Err(QvdError::new(
+            QvdErrorKind::InvalidUtf8 { column: header.field_name.clone(), symbol_index, offset },
+            format!(
+                "symbol {symbol_index} of field '{}' is not valid UTF-8 at byte {offset} of its symbol table",
+                header.field_name,
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Decodes a .qvd file held in memory, text symbols borrow from `bytes`.
+pub(crate) fn read_qvd_ref<'a>(bytes: &'a [u8], options: &QvdReadOptions) -> Result<(QvdMetadata, Vec<ColumnRef<'a>>), QvdError> {
+    #[cfg(feature = "tracing")]
+    let _read_span = tracing::info_span!("read_qvd").entered();
+
+    let mut body = bytes;
+    let qvd_structure = read_header(&mut body, &mut Vec::new())?;
+    let columns = decode_fields(&qvd_structure, body, options, |field| {
+        check_field_utf8(field.field_header, field.field_buf, options)?;
+        decode_symbols(field.field_buf, options)
+    })?
+        .into_iter()
+        .map(|(header, symbols, indexes)| ColumnRef { header, symbols, indexes })
+        .collect();
+
+    Ok((metadata(&qvd_structure, body.len() - qvd_structure.offset), columns))
+}
+
+/// Splits the data following the header into the symbol tables and the row section.
+pub(crate) fn split_body<'a>(qvd_structure: &QvdTableHeader, body: &'a [u8]) -> Result<(&'a [u8], &'a [u8]), QvdError> {
+    if qvd_structure.offset > body.len() {
+        return Err(QvdError::new(
+            QvdErrorKind::Truncated,
+            format!("header declares offset {} but only {} bytes follow the header", qvd_structure.offset, body.len()),
+        ));
+    }
+    check_field_regions(qvd_structure)?;
+    Ok(body.split_at(qvd_structure.offset))
+}
+
+/// Checks that the symbols of every field lie within the symbol tables, that
+/// no two fields share bytes and that the last symbol table ends at the
+/// declared offset, where the row section starts.
+pub(crate) fn check_field_regions(qvd_structure: &QvdTableHeader) -> Result<(), QvdError> {
+    let mut regions = Vec::with_capacity(qvd_structure.fields.headers.len());
+    for header in &qvd_structure.fields.headers {
+        let end = header.offset.checked_add(header.length).filter(|&end| end <= qvd_structure.offset)
+            .ok_or_else(|| QvdError::new(
+                QvdErrorKind::FieldOutOfBounds { field: header.field_name.clone() },
+                format!("symbols of field '{}' lie outside the symbol table", header.field_name),
+            ))?;
+        if header.length > 0 {
+            regions.push((header.offset, end, &header.field_name));
+        }
+    }
+    regions.sort_unstable();
+    for pair in regions.windows(2) {
+        let ((_, end, field), (start, _, other)) = (pair[0], pair[1]);
+        if start < end {
+            return Err(QvdError::new(
+                QvdErrorKind::OverlappingFields { field: field.clone(), other: other.clone() },
+                format!("symbols of fields '{field}' and '{other}' overlap"),
+            ));
+        }
+    }
+    // Symbols are always stored before the rows, a gap means the declared
+    // boundary doesn't match the field layout
+    let symbols_end = regions.last().map_or(0, |&(_, end, _)| end);
+    if symbols_end != qvd_structure.offset {
+        return Err(QvdError::new(
+            QvdErrorKind::InvalidFormat,
+            format!("symbol tables end at byte {symbols_end} but the header declares offset {}", qvd_structure.offset),
+        ));
+    }
+    Ok(())
+}
+
+// Decoded header, symbols and row indexes of one field
+type DecodedField<T> = (Header, Vec<T>, Vec<isize>);
 
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf).unwrap();
-    let (symbol_map, row_section) = buf.split_at(qvd_structure.offset);
+// Splits the data following the header into symbol tables and rows, then
+// decodes every field in parallel with `decode_symbols`
+fn decode_fields<'a, T, F>(qvd_structure: &QvdTableHeader, body: &'a [u8], options: &QvdReadOptions, decode_symbols: F) -> Result<Vec<DecodedField<T>>, QvdError>
+where
+    T: Send,
+    F: Fn(&Field<'_, 'a>) -> Result<Vec<T>, QvdError> + Sync,
+{
+    let (symbol_map, row_section) = split_body(qvd_structure, body)?;
+    check_trailing_bytes(qvd_structure, row_section.len(), options)?;
     let record_byte_size = qvd_structure.record_byte_size;
+    let no_of_records = qvd_structure.no_of_records;
+    // rayon workers don't inherit the current span, so parent explicitly
+    #[cfg(feature = "tracing")]
+    let read_span = tracing::Span::current();
 
     let fields: Vec<Field> = qvd_structure.fields.headers.iter().map(|field_header| {
         Field::from_header_and_symbol_map(field_header, symbol_map)
-    }).collect();
+    }).collect::<Result<_, QvdError>>()?;
+
+    options.install(|| fields.into_par_iter().map(|field| {
+        #[cfg(feature = "tracing")]
+        let symbols_span = tracing::info_span!(
+            parent: &read_span, "decode_symbols",
+            field = %field.field_header.field_name, bytes = field.field_buf.len(), symbols = tracing::field::Empty,
+        ).entered();
+        let symbols = decode_symbols(&field)?;
+        #[cfg(feature = "tracing")]
+        {
+            symbols_span.record("symbols", symbols.len());
+            symbols_span.exit();
+        }
+
+        #[cfg(feature = "tracing")]
+        let indexes_span = tracing::info_span!(
+            parent: &read_span, "decode_indexes",
+            field = %field.field_header.field_name, bytes = row_section.len(), rows = tracing::field::Empty,
+        ).entered();
+        let indexes = field_indexes(row_section, field.field_header, record_byte_size, no_of_records)?;
+        #[cfg(feature = "tracing")]
+        indexes_span.record("rows", indexes.len());
+        check_index_range(field.field_header, &indexes, symbols.len(), options)?;
+
+        Ok((Header(field.field_header.field_name.clone()), symbols, indexes))
+    }).collect())
+}
+
+pub(crate) fn read_qvd_columns(file_name: impl AsRef<Path>, columns: &[&str], options: &QvdReadOptions) -> Result<(QvdMetadata, Vec<Column>), QvdError> {
+    let file = File::open(&file_name)?;
+    let mut reader = BufReader::new(file);
+    read_columns_from_reader(&mut reader, columns, options)
+}
+
+/// Reads the symbol tables of `columns` only, seeking past those of the other
+/// fields. The row section holds every field, so it is read in full.
+fn read_columns_from_reader<R: BufRead + Seek>(reader: &mut R, columns: &[&str], options: &QvdReadOptions) -> Result<(QvdMetadata, Vec<Column>), QvdError> {
+    let qvd_structure = read_header(reader, &mut Vec::new())?;
+    let body_start = reader.stream_position()?;
+    let headers = columns.iter().map(|&name| {
+        qvd_structure.fields.headers.iter()
+            .find(|header| header.field_name == name)
+            .ok_or_else(|| column_not_found(name))
+    }).collect::<Result<Vec<_>, QvdError>>()?;
+
+    check_field_regions(&qvd_structure)?;
+    let symbol_tables = headers.iter().map(|header| {
+        reader.seek(SeekFrom::Start(body_start + header.offset as u64))?;
+        let mut field_buf = Vec::with_capacity(header.length);
+        reader.by_ref().take(header.length as u64).read_to_end(&mut field_buf)?;
+        if field_buf.len() < header.length {
+            return Err(QvdError::new(
+                QvdErrorKind::Truncated,
+                format!("symbols of field '{}' extend past the end of the file", header.field_name),
+            ));
+        }
+        Ok(field_buf)
+    }).collect::<Result<Vec<_>, QvdError>>()?;
+
+    reader.seek(SeekFrom::Start(body_start + qvd_structure.offset as u64))?;
+    let mut row_section = Vec::new();
+    reader.read_to_end(&mut row_section)?;
+    check_trailing_bytes(&qvd_structure, row_section.len(), options)?;
+
+    let record_byte_size = qvd_structure.record_byte_size;
+    let no_of_records = qvd_structure.no_of_records;
+    let columns = options.install(|| headers.par_iter().zip(symbol_tables).map(|(header, field_buf)| {
+        check_field_utf8(header, &field_buf, options)?;
+        Ok(Column {
+            header: Header(header.field_name.clone()),
+            symbols: get_column_values_from_buf(&field_buf, options)?,
+            indexes: field_indexes(&row_section, header, record_byte_size, no_of_records)?,
+        }).and_then(|column| {
+            check_index_range(header, &column.indexes, column.symbols.len(), options)?;
+            Ok(column)
+        })
+    }).collect::<Result<_, QvdError>>())?;
+
+    Ok((metadata(&qvd_structure, row_section.len()), columns))
+}
+
+/// Decodes the symbol tables once, then reads the row section `chunk_size`
+/// records at a time, so only one chunk of rows is resolved at any time.
+pub(crate) fn read_qvd_chunked<F>(file_name: impl AsRef<Path>, chunk_size: usize, options: &QvdReadOptions, mut f: F) -> Result<(), QvdError>
+where
+    F: FnMut(&[Vec<&CellValue>]),
+{
+    let file = File::open(&file_name)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let qvd_structure = read_header(&mut reader, &mut Vec::new())?;
+    let mut columns = read_symbol_tables(&mut reader, &qvd_structure, options)?;
+    let headers = &qvd_structure.fields.headers;
+
+    let record_byte_size = qvd_structure.record_byte_size;
+    let no_of_records = qvd_structure.no_of_records;
+    // The header is untrusted, so check the records fit the file before
+    // sizing the chunk buffer from it. A chunk never holds more than every
+    // record, so it is no larger than the records.
+    streamed_records_len(&qvd_structure, file_len.saturating_sub(reader.stream_position()?))?;
+    let chunk_size = chunk_size.clamp(1, no_of_records.max(1));
+    let mut chunk = Vec::with_capacity(chunk_size * record_byte_size);
+    let mut remaining = no_of_records;
+    while remaining > 0 {
+        let rows = remaining.min(chunk_size);
+        chunk.resize(rows * record_byte_size, 0);
+        reader.read_exact(&mut chunk)?;
+        for (column, field_header) in columns.iter_mut().zip(headers) {
+            column.indexes = field_indexes(&chunk, field_header, record_byte_size, rows)?;
+            check_index_range(field_header, &column.indexes, column.symbols.len(), options)?;
+        }
+        let resolved: Vec<Vec<_>> = (0..rows)
+            .map(|row| columns.iter().map(|col| col.resolve_row(row)).collect())
+            .collect();
+        f(&resolved);
+        remaining -= rows;
+    }
+    if options.reject_trailing_bytes && reader.read(&mut [0])? > 0 {
+        return Err(trailing_bytes_error(&qvd_structure));
+    }
+    Ok(())
+}
+
+/// Decodes the symbol tables of every field and stops, the row section is
+/// never read.
+pub(crate) fn read_qvd_dictionaries(file_name: impl AsRef<Path>, options: &QvdReadOptions) -> Result<Vec<(String, Vec<CellValue>)>, QvdError> {
+    let file = File::open(&file_name)?;
+    let mut reader = BufReader::new(file);
+    let qvd_structure = read_header(&mut reader, &mut Vec::new())?;
+    let columns = read_symbol_tables(&mut reader, &qvd_structure, options)?;
+    Ok(columns.into_iter().map(|col| (col.header.0, col.symbols)).collect())
+}
+
+/// Keeps a uniform random sample of `n` records while streaming the row
+/// section (reservoir sampling), so only `n` records are held at any time.
+/// The sample is returned in file order and is the same for the same `seed`.
+pub(crate) fn read_qvd_sample(file_name: impl AsRef<Path>, n: usize, seed: u64, options: &QvdReadOptions) -> Result<(QvdMetadata, Vec<Column>), QvdError> {
+    let file = File::open(&file_name)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let qvd_structure = read_header(&mut reader, &mut Vec::new())?;
+    let mut columns = read_symbol_tables(&mut reader, &qvd_structure, options)?;
+    let record_byte_size = qvd_structure.record_byte_size;
+    let no_of_records = qvd_structure.no_of_records;
+    let sample_size = n.min(no_of_records);
+
+    // The header is untrusted, so check the records fit the file before
+    // allocating for them
+    let records_len = streamed_records_len(&qvd_structure, file_len.saturating_sub(reader.stream_position()?))?;
+    // Tables with a record size of 0 store no records, every row is the same
+    let stored_records = if record_byte_size == 0 { 0 } else { no_of_records };
 
-    let columns = fields.into_par_iter().map(|field| {
-        Column {
-            header: Header(field.field_header.field_name.clone()),
-            symbols: field.get_column_values(),
-            indexes: get_row_indexes(row_section, field.field_header, record_byte_size),
+    // Row index and bytes of every sampled record, slot by slot. Both sizes
+    // are bounded by `records_len`, which fits in the file.
+    let mut sampled_rows = Vec::with_capacity(sample_size.min(stored_records));
+    let mut reservoir = vec![0; sample_size.min(stored_records) * record_byte_size];
+    let mut record = vec![0; record_byte_size.min(records_len)];
+    let mut rng = SplitMix64(seed);
+    for row in 0..stored_records {
+        reader.read_exact(&mut record).map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => QvdError::new(
+                QvdErrorKind::Truncated,
+                format!("header declares {no_of_records} records but the file ends after {row}"),
+            ),
+            _ => err.into(),
+        })?;
+        let slot = if row < sample_size { row } else { rng.below(row as u64 + 1) as usize };
+        if slot < sample_size {
+            if slot == sampled_rows.len() {
+                sampled_rows.push(row);
+            } else {
+                sampled_rows[slot] = row;
+            }
+            reservoir[slot * record_byte_size..(slot + 1) * record_byte_size].copy_from_slice(&record);
         }
-    }).collect();
+    }
+    let trailing = io::copy(&mut reader, &mut io::sink())?;
+    let data_section_len = records_len + trailing as usize;
+    check_trailing_bytes(&qvd_structure, data_section_len, options)?;
+
+    let mut slots: Vec<usize> = (0..sampled_rows.len()).collect();
+    slots.sort_unstable_by_key(|&slot| sampled_rows[slot]);
+    let records: Vec<u8> = slots.iter()
+        .flat_map(|&slot| &reservoir[slot * record_byte_size..(slot + 1) * record_byte_size])
+        .copied()
+        .collect();
+    for (column, header) in columns.iter_mut().zip(&qvd_structure.fields.headers) {
+        column.indexes = field_indexes(&records, header, record_byte_size, sample_size)?;
+        check_index_range(header, &column.indexes, column.symbols.len(), options)?;
+    }
+    Ok((metadata(&qvd_structure, data_section_len), columns))
+}
+
+// SplitMix64, small and reproducible across platforms, enough for sampling
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
 
-    Ok(columns)
+    // Maps into `0..bound` by multiplication, the bias is negligible for row counts
+    fn below(&mut self, bound: u64) -> u64 {
+        ((self.next() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+// Reads the symbol tables following the header and decodes them into columns
+// without any rows
+fn read_symbol_tables<R: Read>(reader: &mut R, qvd_structure: &QvdTableHeader, options: &QvdReadOptions) -> Result<Vec<Column>, QvdError> {
+    let mut symbol_map = Vec::new();
+    reader.take(qvd_structure.offset as u64).read_to_end(&mut symbol_map)?;
+    if symbol_map.len() < qvd_structure.offset {
+        return Err(QvdError::new(
+            QvdErrorKind::Truncated,
+            format!("header declares offset {} but only {} bytes follow the header", qvd_structure.offset, symbol_map.len()),
+        ));
+    }
+    check_field_regions(qvd_structure)?;
+    options.install(|| qvd_structure.fields.headers.par_iter().map(|field_header| {
+        let field = Field::from_header_and_symbol_map(field_header, &symbol_map)?;
+        Ok(Column {
+            header: Header(field_header.field_name.clone()),
+            symbols: field.get_column_values(options)?,
+            indexes: Vec::new(),
+        })
+    }).collect())
+}
+
+// Only records are read from the row section, anything after them is ignored
+// unless the options reject it
+fn check_trailing_bytes(qvd_structure: &QvdTableHeader, row_section_len: usize, options: &QvdReadOptions) -> Result<(), QvdError> {
+    let declared = qvd_structure.no_of_records.checked_mul(qvd_structure.record_byte_size);
+    if options.reject_trailing_bytes && declared.is_some_and(|declared| row_section_len > declared) {
+        return Err(trailing_bytes_error(qvd_structure));
+    }
+    Ok(())
+}
+
+fn trailing_bytes_error(qvd_structure: &QvdTableHeader) -> QvdError {
+    QvdError::new(
+        QvdErrorKind::InvalidFormat,
+        format!(
+            "bytes follow the {} records of {} bytes the header declares",
+            qvd_structure.no_of_records, qvd_structure.record_byte_size,
+        ),
+    )
+}
 
+pub(crate) fn read_header<R: BufRead>(reader: &mut R, xml_buf: &mut Vec<u8>) -> Result<QvdTableHeader, QvdError> {
+    let xml = get_xml_data(reader, xml_buf)?;
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("parse_xml", bytes = xml.len()).entered();
+    let qvd_structure: QvdTableHeader = from_str(xml)
+        .map_err(|err| QvdError::new(QvdErrorKind::InvalidHeader, format!("invalid xml header: {err}")))?;
+    Ok(qvd_structure)
 }
 
-fn get_xml_data(reader: &mut BufReader<File>) -> Result<String, io::Error> {
-    let mut buffer = Vec::new();
+pub(crate) fn metadata(qvd_structure: &QvdTableHeader, data_section_len: usize) -> QvdMetadata {
+    QvdMetadata {
+        table_name: qvd_structure.table_name.clone(),
+        creator_doc: qvd_structure.creator_doc.clone(),
+        no_of_records: qvd_structure.no_of_records,
+        record_byte_size: qvd_structure.record_byte_size,
+        offset: qvd_structure.offset,
+        length: qvd_structure.length,
+        data_section_len,
+        field_tags: qvd_structure.fields.headers.iter()
+            .map(|header| (header.field_name.clone(), header.tags.clone()))
+            .collect(),
+    }
+}
+
+/// The XML header of a file, without the separator before the data.
+pub(crate) fn read_raw_header(file_name: impl AsRef<Path>) -> Result<String, QvdError> {
+    let file = File::open(&file_name)?;
+    let mut reader = BufReader::new(file);
+    let mut xml_buf = Vec::new();
+    let xml = get_xml_data(&mut reader, &mut xml_buf)?;
+    Ok(xml.trim_end_matches(['\r', '\n', '\0']).to_string())
+}
+
+fn get_xml_data<'b, R: BufRead>(reader: &mut R, buffer: &'b mut Vec<u8>) -> Result<&'b str, QvdError> {
+    buffer.clear();
     // There is a line break, carriage return and a null terminator between the XMl and data
     // Find the null terminator
-    reader.read_until(0, &mut buffer)
-        .expect("Failed to read file");
-    let xml_string =
-        String::from_utf8(buffer).expect("xml section contains invalid UTF-8 chars");
-    Ok(xml_string)
+    reader.read_until(0, buffer)?;
+    let xml_string = std::str::from_utf8(buffer)?;
+    // Some exporters put a BOM or whitespace before `<?xml`. It is only stripped
+    // from the header, the data section still starts after the null terminator.
+    Ok(xml_string.trim_start_matches(|c: char| c == '\u{feff}' || c.is_whitespace() || c.is_control()))
 }
 
-struct Field<'a> {
-    field_header: &'a QvdFieldHeader,
-    field_buf: &'a [u8],
+pub(crate) struct Field<'h, 'a> {
+    pub(crate) field_header: &'h QvdFieldHeader,
+    pub(crate) field_buf: &'a [u8],
 }
 
-impl<'a> Field<'a> {
-    fn from_header_and_symbol_map(header: &'a QvdFieldHeader, buf: &'a [u8]) -> Self {
+impl<'h, 'a> Field<'h, 'a> {
+    pub(crate) fn from_header_and_symbol_map(header: &'h QvdFieldHeader, buf: &'a [u8]) -> Result<Self, QvdError> {
         let start = header.offset;
-        let end = start + header.length;
-        let field_buf = &buf[start..end];
-        Self { 
+        let field_buf = start.checked_add(header.length)
+            .and_then(|end| buf.get(start..end))
+            .ok_or_else(|| QvdError::new(
+                QvdErrorKind::FieldOutOfBounds { field: header.field_name.clone() },
+                format!("symbols of field '{}' lie outside the symbol table", header.field_name),
+            ))?;
+        Ok(Self { 
             field_header: 
             header, field_buf,
-        }
+        })
+    }
+
+    fn get_column_values(&self, options: &QvdReadOptions) -> Result<Vec<CellValue>, QvdError> {
+        check_field_utf8(self.field_header, self.field_buf, options)?;
+        get_column_values_from_buf(self.field_buf, options)
     }
+}
+
+/// Symbol tables of at least this many bytes are decoded in parallel, if
+/// there is more than one thread to decode them on.
+const PARALLEL_SYMBOLS_MIN_BYTES: usize = 1 << 20;
+
+fn get_column_values_from_buf(field_buf: &[u8], options: &QvdReadOptions) -> Result<Vec<CellValue>, QvdError> {
+    // Fields holding only nulls store no symbols at all
+    if field_buf.is_empty() {
+        return Ok(Vec::new());
+    }
+    if field_buf.len() >= PARALLEL_SYMBOLS_MIN_BYTES && rayon::current_num_threads() > 1 {
+        return decode_symbols_par(field_buf, options);
+    }
+    let symbols = decode_symbols(field_buf, options)?;
+    Ok(symbols.into_iter().map(CellValue::from).collect())
+}
+
+// Symbols have variable length, so a first pass only finds where each one
+// starts, then the symbols are decoded in parallel
+fn decode_symbols_par(field_buf: &[u8], options: &QvdReadOptions) -> Result<Vec<CellValue>, QvdError> {
+    symbol_positions(field_buf)?
+        .into_par_iter()
+        .map(|pos| decode_symbol(field_buf, pos, options).map(|(value, _)| CellValue::from(value)))
+        .collect()
+}
 
-    fn get_column_values(&self) -> Vec<CellValue> {
-        get_column_values_from_buf(self.field_buf)
+// Start of every symbol from the type bytes and string terminators, without
+// decoding or validating the values themselves
+fn symbol_positions(field_buf: &[u8]) -> Result<Vec<usize>, QvdError> {
+    let mut positions = Vec::new();
+    let mut pos = 0;
+    while pos < field_buf.len() {
+        positions.push(pos);
+        pos = match field_buf[pos] {
+            1 => pos + 5,
+            2 => pos + 9,
+            4 => text_end(field_buf, pos + 1)? + 1,
+            5 => text_end(field_buf, pos + 5)? + 1,
+            6 => text_end(field_buf, pos + 9)? + 1,
+            other => return Err(unknown_symbol(other, pos)),
+        };
     }
+    Ok(positions)
 }
 
-fn get_column_values_from_buf(field_buf: &[u8]) -> Vec<CellValue> {
+fn decode_symbols<'a>(field_buf: &'a [u8], options: &QvdReadOptions) -> Result<Vec<CellValueRef<'a>>, QvdError> {
     let mut i = 0;
-    let mut string_start: usize = 0;
     let mut cell_values = Vec::new();
     while i < field_buf.len() {
-        let byte = &field_buf[i];
-        // Check first byte of symbol. This is not part of the symbol but tells us what type of data to read.
-        match byte {
-            0 => {
-                // Strings are null terminated
-                // Read bytes from start fo string (string_start) up to current byte.
-                let value = string_from_buf(field_buf, string_start, i);
-                match value {
-                    Ok(s) => {
-                        if let Ok(int) = s.parse::<i32>() {
-                            cell_values.push(CellValue::Int(int));
-                        } else if let (Ok(float), Ok(n)) = (s.parse::<f64>(), s.parse::<u128>()) {
-                            if n >= MAX_EXACT_F64 {
-                                cell_values.push(CellValue::Text(s.into()))
-                            } else {
-                                cell_values.push(CellValue::Float(float))
-                            }
-                        } else {
-                            cell_values.push(CellValue::Text(s.into()))
-                        }
-                    },
-                    Err(_) => cell_values.push(CellValue::Null),
-                }
-                i += 1;
-            }
-            1 => {
-                // 4 byte integer
-                let numeric_value = int_from_buf(field_buf, i);
-                cell_values.push(CellValue::Int(numeric_value));
-                i += 5;
-            }
-            2 => {
-                // 4 byte double
-                let numeric_value = float_from_buf(field_buf, i);
-                cell_values.push(CellValue::Float(numeric_value));
-                i += 9;
-            }
-            4 => {
-                // Beginning of a null terminated string type
-                // Mark where string value starts, excluding preceding byte 0x04
-                i += 1;
-                string_start = i;
-            }
-            5 => {
-                // 4 bytes of unknown followed by null terminated string
-                // Skip the 4 bytes before string
-                i += 5;
-                string_start = i;
+        let (value, next) = decode_symbol(field_buf, i, options)?;
+        cell_values.push(value);
+        i = next;
+    }
+    Ok(cell_values)
+}
+
+/// Decodes the symbol starting at byte `pos` of a field's symbol table,
+/// returning it and the position of the following symbol.
+pub(crate) fn decode_symbol<'a>(field_buf: &'a [u8], pos: usize, options: &QvdReadOptions) -> Result<(CellValueRef<'a>, usize), QvdError> {
+    // The first byte of a symbol is not part of its value but tells us what type of data to read.
+    match field_buf[pos] {
+        1 => {
+            // 4 byte integer
+            let numeric_value = int_from_buf(field_buf, pos)?;
+            if options.unsigned_ints && numeric_value < 0 {
+                return Ok((CellValueRef::Float(numeric_value as u32 as f64), pos + 5));
             }
-            6 => {
-                // 8 bytes of unknown followed by null terminated string
-                // Skip the 8 bytes before string
-                i += 9;
-                string_start = i;
+            Ok((CellValueRef::Int(numeric_value), pos + 5))
+        }
+        2 => {
+            // 8 byte double
+            let numeric_value = float_from_buf(field_buf, pos)?;
+            if options.reject_non_finite_floats && !numeric_value.is_finite() {
+                return Err(QvdError::new(
+                    QvdErrorKind::InvalidValue,
+                    format!("non-finite double {numeric_value} at byte {pos} of the symbol table"),
+                ));
             }
-            _ => {
-                // Part of a string, do nothing until null terminator
-                i += 1;
+            let in_range = numeric_value >= i32::MIN as f64 && numeric_value <= i32::MAX as f64;
+            if options.coerce_integral_floats && numeric_value.fract() == 0. && in_range {
+                return Ok((CellValueRef::Int(numeric_value as i32), pos + 9));
             }
+            Ok((CellValueRef::Float(numeric_value), pos + 9))
+        }
+        // Null terminated string, excluding the preceding byte 0x04
+        4 => string_symbol(field_buf, pos + 1),
+        // 4 byte integer followed by its null terminated display text
+        5 => {
+            let int = int_from_buf(field_buf, pos)?;
+            let number = if options.unsigned_ints { int as u32 as f64 } else { int as f64 };
+            dual_symbol(field_buf, number, pos + 5)
         }
+        // 8 byte double followed by its null terminated display text
+        6 => dual_symbol(field_buf, float_from_buf(field_buf, pos)?, pos + 9),
+        other => Err(unknown_symbol(other, pos)),
+    }
+}
+
+fn unknown_symbol(symbol_type: u8, pos: usize) -> QvdError {
+    QvdError::new(
+        QvdErrorKind::InvalidSymbol { offset: pos },
+        format!("unknown symbol type {symbol_type} at byte {pos} of the symbol table"),
+    )
+}
+
+// A string symbol whose text starts at `start`. Text that isn't valid UTF-8 decodes as null.
+fn string_symbol(field_buf: &[u8], start: usize) -> Result<(CellValueRef<'_>, usize), QvdError> {
+    let (text, next) = symbol_text(field_buf, start)?;
+    let value = match text {
+        Some(s) => parse_number(s).unwrap_or(CellValueRef::Text(s)),
+        None => CellValueRef::Null,
+    };
+    Ok((value, next))
+}
+
+// A number with display text starting at `start`. Numeric text, which is
+// how QlikView stores most numbers, decodes like a string symbol; any other
+// text, e.g. a formatted date or "007", is kept as a `Dual` along with the number.
+fn dual_symbol(field_buf: &[u8], number: f64, start: usize) -> Result<(CellValueRef<'_>, usize), QvdError> {
+    let (text, next) = symbol_text(field_buf, start)?;
+    let value = match text {
+        Some(s) => canonical_number(s).unwrap_or(CellValueRef::Dual(number, s)),
+        None => CellValueRef::Null,
+    };
+    Ok((value, next))
+}
+
+// Null terminated text starting at `start`, `None` if it isn't valid UTF-8,
+// and the position after the terminator.
+fn symbol_text(field_buf: &[u8], start: usize) -> Result<(Option<&str>, usize), QvdError> {
+    let end = text_end(field_buf, start)?;
+    Ok((string_from_buf(field_buf, start, end).ok(), end + 1))
+}
+
+// Position of the null terminator of text starting at `start`
+fn text_end(field_buf: &[u8], start: usize) -> Result<usize, QvdError> {
+    field_buf.get(start..)
+        .and_then(|rest| rest.iter().position(|&b| b == 0))
+        .map(|len| start + len)
+        .ok_or_else(|| QvdError::new(
+            QvdErrorKind::InvalidSymbol { offset: start },
+            format!("string symbol at byte {start} is not terminated"),
+        ))
+}
+
+// Dual text stands in for its number only if it is exactly how that number
+// displays, so text such as "007" or "1e3" keeps its spelling.
+fn canonical_number(s: &str) -> Option<CellValueRef<'static>> {
+    parse_number(s).filter(|number| match number {
+        CellValueRef::Int(i) => i.to_string() == s,
+        CellValueRef::Float(f) => f.to_string() == s,
+        _ => false,
+    })
+}
+
+/// Parses text the way string symbols are decoded: as `Int` if it fits an
+/// `i32`, else as a finite `Float`. Integers of 2^53 and above can't be
+/// represented exactly and are rejected, as are NaN and infinities.
+pub(crate) fn parse_number(s: &str) -> Option<CellValueRef<'static>> {
+    if let Ok(int) = s.parse::<i32>() {
+        return Some(CellValueRef::Int(int));
+    }
+    let float = s.parse::<f64>().ok().filter(|f| f.is_finite())?;
+    if s.parse::<u128>().is_ok_and(|n| n >= MAX_EXACT_F64) {
+        None
+    } else {
+        Some(CellValueRef::Float(float))
     }
-    cell_values
 }
 
 fn string_from_buf(field_buf: &[u8], string_start: usize, end: usize) -> Result<&str, QvdError> {
@@ -142,67 +767,700 @@ fn string_from_buf(field_buf: &[u8], string_start: usize, end: usize) -> Result<
     Ok(s)
 }
 
-fn int_from_buf(field_buf: &[u8], pos: usize) -> i32 {
-    let target_bytes =  &field_buf[pos + 1..pos + 5];
-    let byte_array: [u8; 4] = target_bytes.try_into().unwrap();
-    i32::from_le_bytes(byte_array)
+fn int_from_buf(field_buf: &[u8], pos: usize) -> Result<i32, QvdError> {
+    symbol_bytes(field_buf, pos).map(i32::from_le_bytes)
+}
+
+fn float_from_buf(field_buf: &[u8], pos: usize) -> Result<f64, QvdError> {
+    symbol_bytes(field_buf, pos).map(f64::from_le_bytes)
 }
 
-fn float_from_buf(field_buf: &[u8], pos: usize) -> f64 {
-    let target_bytes =  &field_buf[pos + 1..pos + 9];
-    let byte_array: [u8; 8] = target_bytes.try_into().unwrap();
-    f64::from_le_bytes(byte_array)
+// The N bytes following the type byte at `pos`
+fn symbol_bytes<const N: usize>(field_buf: &[u8], pos: usize) -> Result<[u8; N], QvdError> {
+    field_buf.get(pos + 1..pos + 1 + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| QvdError::new(
+            QvdErrorKind::InvalidSymbol { offset: pos },
+            format!("symbol at byte {pos} runs past the end of the field"),
+        ))
 }
 
 
+fn field_indexes(row_section: &[u8], field: &QvdFieldHeader, record_byte_size: usize, no_of_records: usize) -> Result<Vec<isize>, QvdError> {
+    // Tables where every field has a single value may not store any record bytes
+    if record_byte_size == 0 {
+        let mut indexes = Vec::new();
+        indexes.try_reserve_exact(no_of_records).map_err(|_| QvdError::new(
+            QvdErrorKind::InvalidFormat,
+            format!("header declares {no_of_records} records"),
+        ))?;
+        indexes.resize(no_of_records, field.bias);
+        return Ok(indexes);
+    }
+    check_bit_layout(field, record_byte_size)?;
+    let rows = records(row_section, record_byte_size, no_of_records)?;
+    Ok(get_row_indexes(rows, field, record_byte_size))
+}
+
+pub(crate) fn check_bit_layout(field: &QvdFieldHeader, record_byte_size: usize) -> Result<(), QvdError> {
+    if field.bit_width > 32 || field.bit_offset.saturating_add(field.bit_width) > record_byte_size.saturating_mul(8) {
+        return Err(QvdError::new(
+            QvdErrorKind::InvalidBitLayout { field: field.field_name.clone() },
+            format!(
+                "field '{}' with bit offset {} and width {} does not fit a {record_byte_size} byte record",
+                field.field_name, field.bit_offset, field.bit_width,
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// The bytes of the declared records at the start of the row section
+pub(crate) fn records(row_section: &[u8], record_byte_size: usize, no_of_records: usize) -> Result<&[u8], QvdError> {
+    no_of_records.checked_mul(record_byte_size)
+        .and_then(|len| row_section.get(..len))
+        .ok_or_else(|| QvdError::new(
+            QvdErrorKind::Truncated,
+            format!("header declares {no_of_records} records of {record_byte_size} bytes but only {} bytes follow", row_section.len()),
+        ))
+}
+
+/// `records` for a row section that is streamed instead of held in memory:
+/// checks the declared records fit in the `row_section_len` bytes left and
+/// returns their length.
+fn streamed_records_len(qvd_structure: &QvdTableHeader, row_section_len: u64) -> Result<usize, QvdError> {
+    let (no_of_records, record_byte_size) = (qvd_structure.no_of_records, qvd_structure.record_byte_size);
+    let records_len = no_of_records.checked_mul(record_byte_size).ok_or_else(|| QvdError::new(
+        QvdErrorKind::InvalidFormat,
+        format!("header declares {no_of_records} records of {record_byte_size} bytes"),
+    ))?;
+    if records_len as u64 > row_section_len {
+        return Err(QvdError::new(
+            QvdErrorKind::Truncated,
+            format!("header declares {no_of_records} records of {record_byte_size} bytes but only {row_section_len} bytes follow"),
+        ));
+    }
+    Ok(records_len)
+}
 
 // Retrieve bit stuffed data. Each row has index to value from symbol map.
 fn get_row_indexes(buf: &[u8], field: &QvdFieldHeader, record_byte_size: usize) -> Vec<isize> {
     let mut indexes: Vec<isize> = Vec::with_capacity(buf.len() / record_byte_size);
-    for chunk in buf.chunks(record_byte_size) {
-        let mut chunk = chunk.to_vec();
-        chunk.reverse();
-
-        let bits = BitSlice::<Msb0, _>::from_slice(&chunk).unwrap();
-        let start = bits.len() - field.bit_offset;
-        let end = start - field.bit_width;
-        let index = bitslice_to_u32(&bits[end..start]);
-        indexes.push(index  + field.bias);
+    for record in buf.chunks_exact(record_byte_size) {
+        indexes.push(record_index(record, field));
     }
     indexes
 }
 
-fn bitslice_to_u32(slice: &BitSlice::<Msb0, u8>) -> isize {
-    slice.iter().fold(0, |acc, &bit| (acc << 1) | bit as isize)
+/// Symbol index of `field` in one record, the layout must have passed `check_bit_layout`.
+pub(crate) fn record_index(record: &[u8], field: &QvdFieldHeader) -> isize {
+    let mut chunk = record.to_vec();
+    chunk.reverse();
+
+    let bits = BitSlice::<Msb0, _>::from_slice(&chunk).unwrap();
+    let start = bits.len() - field.bit_offset;
+    let end = start - field.bit_width;
+    let index = bitslice_to_u32(&bits[end..start]);
+    // Widen so the sum can't overflow where isize has 32 bits. Indexes outside
+    // isize can't address a symbol and saturate, reading as null or past the
+    // end of the symbol table.
+    let biased = i64::from(index).saturating_add(field.bias as i64);
+    biased.clamp(isize::MIN as i64, isize::MAX as i64) as isize
+}
+
+// An empty slice (a field with a bit width of 0) reads as index 0
+fn bitslice_to_u32(slice: &BitSlice::<Msb0, u8>) -> u32 {
+    slice.iter().fold(0, |acc, &bit| (acc << 1) | bit as u32)
+}
+
+// With `reject_out_of_range_indexes`, fails on the first row pointing past the
+// symbol table or holding a negative index other than `NULL_INDEX`. A field
+// without symbols can only hold nulls, so there every index but `NULL_INDEX`
+// is rejected regardless of the option.
+fn check_index_range(field: &QvdFieldHeader, indexes: &[isize], symbols: usize, options: &QvdReadOptions) -> Result<(), QvdError> {
+    if !options.reject_out_of_range_indexes && symbols > 0 {
+        return Ok(());
+    }
+    let invalid = |idx: isize| match usize::try_from(idx) {
+        Ok(i) => i >= symbols,
+        Err(_) => idx != NULL_INDEX,
+    };
+    match indexes.iter().position(|&idx| invalid(idx)) {
+        Some(row) if indexes[row] < 0 => Err(QvdError::new(
+            QvdErrorKind::InvalidFormat,
+            format!(
+                "row {row} of field '{}' has index {}, only {NULL_INDEX} marks a null",
+                field.field_name, indexes[row],
+            ),
+        )),
+        Some(row) => Err(QvdError::new(
+            QvdErrorKind::InvalidFormat,
+            format!(
+                "row {row} of field '{}' refers to symbol {} but there are only {symbols}",
+                field.field_name, indexes[row],
+            ),
+        )),
+        None => Ok(()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::time::Instant;
+    use std::{io, time::Instant};
 
-    use crate::types::{CellValue, Header};
+    use crate::{test_util::TempFile, types::{CellValue, Header, QvdDocument}};
 
     use super::*;
 
+    fn header_xml(fields: &[QvdFieldHeader], record_byte_size: usize, no_of_records: usize, offset: usize, length: usize) -> String {
+        let fields: String = fields.iter().map(|field| format!(
+            "<QvdFieldHeader><FieldName>{}</FieldName><BitOffset>{}</BitOffset><BitWidth>{}</BitWidth>\
+            <Bias>{}</Bias><Offset>{}</Offset><Length>{}</Length></QvdFieldHeader>",
+            field.field_name, field.bit_offset, field.bit_width, field.bias, field.offset, field.length,
+        )).collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\r\n<QvdTableHeader>\
+            <CreatorDoc>test</CreatorDoc><TableName>test</TableName><Fields>{fields}</Fields>\
+            <RecordByteSize>{record_byte_size}</RecordByteSize><NoOfRecords>{no_of_records}</NoOfRecords>\
+            <Offset>{offset}</Offset><Length>{length}</Length></QvdTableHeader>"
+        )
+    }
+
+    // Header, separator and data section as laid out in a .qvd file
+    fn qvd_bytes(xml: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes = xml.as_bytes().to_vec();
+        bytes.extend_from_slice(b"\r\n\0");
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn int_field(name: &str, offset: usize, length: usize, bit_offset: usize, bit_width: usize) -> QvdFieldHeader {
+        QvdFieldHeader {
+            field_name: name.into(),
+            offset,
+            length,
+            bit_offset,
+            bit_width,
+            bias: 0,
+            tags: Vec::new(),
+        }
+    }
+
+    // Symbols 10 and 20 as 4 byte integers, shared by most synthetic files below
+    const TWO_INTS: [u8; 10] = [0x01, 0x0A, 0x00, 0x00, 0x00, 0x01, 0x14, 0x00, 0x00, 0x00];
+
+    // Header of a table whose only field, "num", indexes TWO_INTS with one byte per record
+    fn num_header(no_of_records: usize) -> String {
+        header_xml(&[int_field("num", 0, TWO_INTS.len(), 0, 1)], 1, no_of_records, TWO_INTS.len(), no_of_records)
+    }
+
+    // `xml` followed by TWO_INTS and the records
+    fn num_file(xml: &str, records: &[u8]) -> Vec<u8> {
+        qvd_bytes(xml, &[&TWO_INTS[..], records].concat())
+    }
+
+    #[test]
+    fn test_synthetic_file() {
+        // rows pointing at 20, 10, 20
+        let bytes = num_file(&num_header(3), &[1, 0, 1]);
+        let (_, columns) = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns[0].header, Header("num".into()));
+        assert_eq!(columns[0].as_values(), vec![&CellValue::Int(20), &CellValue::Int(10), &CellValue::Int(20)]);
+    }
+
+    #[test]
+    fn test_overlapping_fields() {
+        let xml = header_xml(&[int_field("a", 0, 10, 0, 1), int_field("b", 5, 5, 1, 1)], 1, 1, 10, 1);
+        let err = read_qvd_from_reader(&mut num_file(&xml, &[0]).as_slice(), &QvdReadOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::OverlappingFields { field: "a".into(), other: "b".into() });
+
+        let xml = header_xml(&[int_field("a", 0, 5, 0, 1), int_field("b", 5, 5, 1, 1)], 1, 1, 10, 1);
+        assert!(read_qvd_from_reader(&mut num_file(&xml, &[0]).as_slice(), &QvdReadOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        // three records of one byte, then two bytes of padding
+        let bytes = num_file(&num_header(3), &[1, 0, 1, 0xFF, 0xFF]);
+        let (metadata, columns) = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns[0].indexes, vec![1, 0, 1]);
+        assert_eq!(metadata.data_section_len(), 5);
+
+        let strict = QvdReadOptions { reject_trailing_bytes: true, ..Default::default() };
+        let err = read_qvd_from_reader(&mut bytes.as_slice(), &strict).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::InvalidFormat);
+        let exact = num_file(&num_header(3), &[1, 0, 1]);
+        assert!(read_qvd_from_reader(&mut exact.as_slice(), &strict).is_ok());
+    }
+
+    #[test]
+    fn test_field_without_symbols() {
+        let mut empty = int_field("empty", TWO_INTS.len(), 0, 1, 0);
+        empty.bias = -2;
+        let xml = |empty: &QvdFieldHeader| header_xml(&[int_field("num", 0, TWO_INTS.len(), 0, 1), empty.clone()], 1, 2, TWO_INTS.len(), 2);
+        let bytes = num_file(&xml(&empty), &[1, 0]);
+        let (_, columns) = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert!(columns[1].symbols.is_empty());
+        assert_eq!(columns[1].indexes, vec![-2, -2]);
+        assert_eq!(columns[1].as_values(), vec![&CellValue::Null, &CellValue::Null]);
+        let (_, columns) = read_columns_from_reader(&mut io::Cursor::new(&bytes), &["empty"], &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns[0].null_count(), 2);
+
+        // Without a bias of -2 the rows would refer to symbols that don't exist
+        empty.bias = 0;
+        let bytes = num_file(&xml(&empty), &[1, 0]);
+        let err = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::InvalidFormat);
+        assert_eq!(err.message(), "row 0 of field 'empty' refers to symbol 0 but there are only 0");
+
+        // Nor may they hold any negative index but the null sentinel
+        empty.bias = -1;
+        let bytes = num_file(&xml(&empty), &[1, 0]);
+        let err = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap_err();
+        assert_eq!(err.message(), "row 0 of field 'empty' has index -1, only -2 marks a null");
+    }
+
+    #[test]
+    fn test_bias_past_symbols() {
+        // a bias of 5 pushes every row past the two symbols
+        let xml = num_header(2).replace("<Bias>0</Bias>", "<Bias>5</Bias>");
+        let bytes = num_file(&xml, &[0, 1]);
+        let (_, columns) = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns[0].indexes, vec![5, 6]);
+        assert_eq!(columns[0].as_values(), vec![&CellValue::Null, &CellValue::Null]);
+
+        let strict = QvdReadOptions { reject_out_of_range_indexes: true, ..Default::default() };
+        let err = read_qvd_from_reader(&mut bytes.as_slice(), &strict).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::InvalidFormat);
+
+        let mut field = int_field("num", 0, 10, 0, 1);
+        field.bias = isize::MAX;
+        assert_eq!(record_index(&[1], &field), isize::MAX);
+        field.bias = isize::MIN;
+        assert_eq!(record_index(&[1], &field), isize::MIN + 1);
+    }
+
+    #[test]
+    fn test_negative_bias() {
+        // three symbols, raw values 0 and 1 are biased to -2 (null) and -1
+        let symbols = [&TWO_INTS[..], &[0x01, 0x1E, 0x00, 0x00, 0x00]].concat();
+        let mut field = int_field("num", 0, 15, 0, 3);
+        field.bias = -2;
+        let xml = header_xml(&[field], 1, 5, 15, 5);
+        let bytes = qvd_bytes(&xml, &[&symbols[..], &[0, 1, 2, 3, 4]].concat());
+        let (_, columns) = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns[0].indexes, vec![-2, -1, 0, 1, 2]);
+        // -1 is neither the null sentinel nor a symbol, it only reads as null leniently
+        assert_eq!(columns[0].as_values(), vec![
+            &CellValue::Null, &CellValue::Null, &CellValue::Int(10), &CellValue::Int(20), &CellValue::Int(30),
+        ]);
+
+        let strict = QvdReadOptions { reject_out_of_range_indexes: true, ..Default::default() };
+        let err = read_qvd_from_reader(&mut bytes.as_slice(), &strict).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::InvalidFormat);
+        assert_eq!(err.message(), "row 1 of field 'num' has index -1, only -2 marks a null");
+
+        // A negative bias with only the sentinel and valid indexes is accepted
+        let valid = qvd_bytes(&xml, &[&symbols[..], &[0, 2, 3, 4, 0]].concat());
+        let (_, columns) = read_qvd_from_reader(&mut valid.as_slice(), &strict).unwrap();
+        assert_eq!(columns[0].as_values(), vec![
+            &CellValue::Null, &CellValue::Int(10), &CellValue::Int(20), &CellValue::Int(30), &CellValue::Null,
+        ]);
+    }
+
+    #[test]
+    fn test_header_without_optional_elements() {
+        let xml = num_header(3)
+            .replace("<Bias>0</Bias>", "")
+            .replace("<BitOffset>0</BitOffset>", "")
+            .replace("<CreatorDoc>test</CreatorDoc>", "");
+        assert!(!xml.contains("Bias"));
+        let bytes = num_file(&xml, &[1, 0, 1]);
+        let (metadata, columns) = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert_eq!(metadata.creator_doc(), "");
+        assert_eq!(columns[0].as_values(), vec![&CellValue::Int(20), &CellValue::Int(10), &CellValue::Int(20)]);
+    }
+
+    #[test]
+    fn test_header_with_quoted_numbers() {
+        let xml = num_header(3)
+            .replace("<Offset>10</Offset>", "<Offset>\"10\"</Offset>")
+            .replace("<Length>3</Length>", "<Length> 3 </Length>")
+            .replace("<BitWidth>1</BitWidth>", "<BitWidth>'1'</BitWidth>");
+        let bytes = num_file(&xml, &[1, 0, 1]);
+        let (metadata, columns) = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert_eq!((metadata.offset(), metadata.length()), (10, 3));
+        assert_eq!(columns[0].as_values(), vec![&CellValue::Int(20), &CellValue::Int(10), &CellValue::Int(20)]);
+
+        let xml = num_header(3).replace("<Offset>10</Offset>", "<Offset>ten</Offset>");
+        let err = read_qvd_from_reader(&mut num_file(&xml, &[]).as_slice(), &QvdReadOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::InvalidHeader);
+    }
+
+    #[test]
+    fn test_header_only_table() {
+        // symbol table present, but no records
+        let bytes = num_file(&num_header(0), &[]);
+        let (metadata, columns) = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert_eq!(metadata.no_of_records(), 0);
+        assert_eq!(columns[0].symbols.len(), 2);
+        let doc = QvdDocument::from_columns(columns);
+        assert_eq!(doc.row_count(), 0);
+        assert!(doc.rows().next().is_none());
+    }
+
+    #[test]
+    fn test_zero_record_byte_size() {
+        let symbols = [0x01, 0x0A, 0x00, 0x00, 0x00];
+        let xml = header_xml(&[int_field("num", 0, 5, 0, 0)], 0, 2, 5, 0);
+        let bytes = qvd_bytes(&xml, &symbols);
+        let (_, columns) = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns[0].as_values(), vec![&CellValue::Int(10), &CellValue::Int(10)]);
+    }
+
+    #[test]
+    fn test_read_chunked() {
+        let mut chunk_sizes = Vec::new();
+        let mut sum = 0.;
+        read_qvd_chunked("tests/test_file.qvd", 5, &QvdReadOptions::default(), |rows| {
+            chunk_sizes.push(rows.len());
+            sum += rows.iter().filter_map(|row| row[2].to_f64_lossy()).sum::<f64>();
+        }).unwrap();
+        assert_eq!(chunk_sizes, vec![5, 5, 2]);
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert!((sum - doc.column_sum("all_float").unwrap()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_read_chunked_checks_indexes() {
+        // the third row's index 2 points past the two symbols
+        let xml = num_header(3).replace("<BitWidth>1</BitWidth>", "<BitWidth>2</BitWidth>");
+        let file = TempFile::new("chunked_indexes", &num_file(&xml, &[0, 1, 2]));
+        let read_chunked = |options: &QvdReadOptions| {
+            let mut rows = 0;
+            read_qvd_chunked(file.path(), 2, options, |chunk| rows += chunk.len()).map(|_| rows)
+        };
+        assert_eq!(read_chunked(&QvdReadOptions::default()).unwrap(), 3);
+
+        let strict = QvdReadOptions { reject_out_of_range_indexes: true, ..Default::default() };
+        let err = read_chunked(&strict).unwrap_err();
+        assert_eq!(err.message(), "row 0 of field 'num' refers to symbol 2 but there are only 2");
+
+        // A field without symbols rejects -1 in every chunk, strict or not
+        let mut empty = int_field("empty", TWO_INTS.len(), 0, 2, 0);
+        empty.bias = -1;
+        let xml = header_xml(&[int_field("num", 0, TWO_INTS.len(), 0, 2), empty], 1, 3, TWO_INTS.len(), 3);
+        let file = TempFile::new("chunked_zero_symbols", &num_file(&xml, &[0, 1, 2]));
+        let err = read_qvd_chunked(file.path(), 2, &QvdReadOptions::default(), |_| ()).unwrap_err();
+        assert_eq!(err.message(), "row 0 of field 'empty' has index -1, only -2 marks a null");
+    }
+
+    #[test]
+    fn test_read_chunked_untrusted_sizes() {
+        let mut chunk_sizes = Vec::new();
+        read_qvd_chunked("tests/test_file.qvd", usize::MAX, &QvdReadOptions::default(), |rows| chunk_sizes.push(rows.len())).unwrap();
+        assert_eq!(chunk_sizes, vec![12]);
+
+        let read = |record_byte_size: usize| {
+            let xml = header_xml(&[int_field("num", 0, TWO_INTS.len(), 0, 1)], record_byte_size, 3, TWO_INTS.len(), 3);
+            let file = TempFile::new("chunked_untrusted", &num_file(&xml, &[1, 0, 1]));
+            read_qvd_chunked(file.path(), 2, &QvdReadOptions::default(), |_| {})
+        };
+        assert!(read(1).is_ok());
+        assert_eq!(read(1 << 40).unwrap_err().kind(), &QvdErrorKind::Truncated);
+        assert_eq!(read(usize::MAX / 2).unwrap_err().kind(), &QvdErrorKind::InvalidFormat);
+    }
+
+    #[test]
+    fn test_read_sample_untrusted_record_size() {
+        let read_sample = |record_byte_size: usize, no_of_records: usize| {
+            let xml = header_xml(&[int_field("num", 0, TWO_INTS.len(), 0, 1)], record_byte_size, no_of_records, TWO_INTS.len(), 3);
+            let file = TempFile::new("sample_untrusted", &num_file(&xml, &[1, 0, 1]));
+            read_qvd_sample(file.path(), usize::MAX, 1, &QvdReadOptions::default()).map(|(_, columns)| columns)
+        };
+        assert_eq!(read_sample(1, 3).unwrap()[0].indexes.len(), 3);
+        assert_eq!(read_sample(usize::MAX / 2, 3).unwrap_err().kind(), &QvdErrorKind::InvalidFormat);
+        assert_eq!(read_sample(1 << 40, 3).unwrap_err().kind(), &QvdErrorKind::Truncated);
+        assert_eq!(read_sample(1, usize::MAX).unwrap_err().kind(), &QvdErrorKind::Truncated);
+        assert!(read_sample(usize::MAX, 0).unwrap()[0].indexes.is_empty());
+    }
+
+    #[test]
+    fn test_read_in_single_thread_pool() {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let options = QvdReadOptions { thread_pool: Some(Arc::new(pool)), ..Default::default() };
+        let (_, columns) = read_qvd("tests/test_file.qvd", &options).unwrap();
+        let (_, expected) = read_qvd("tests/test_file.qvd", &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns, expected);
+    }
+
+    #[test]
+    fn test_reader_reuses_buffers() {
+        let mut reader = QvdReader::new();
+        let first = reader.read_into("tests/test_file.qvd").unwrap();
+        let capacity = reader.data_buf.capacity();
+        let second = reader.read_into("tests/test_file.qvd").unwrap();
+        assert_eq!(reader.data_buf.capacity(), capacity);
+        assert_eq!(first.columns(), second.columns());
+        assert_eq!(second.columns(), QvdDocument::read("tests/test_file.qvd").unwrap().columns());
+    }
+
+    #[test]
+    fn test_raw_header() {
+        let xml = read_raw_header("tests/test_file.qvd").unwrap();
+        assert!(xml.starts_with("<?xml") || xml.starts_with("<QvdTableHeader"));
+        assert!(xml.ends_with("</QvdTableHeader>"));
+    }
+
+    #[test]
+    fn test_offset_past_end_of_file() {
+        let xml = num_header(3).replace("<Offset>10</Offset>", "<Offset>500</Offset>");
+        let bytes = num_file(&xml, &[1, 0, 1]);
+        let err = read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::Truncated);
+    }
+
+    #[test]
+    fn test_read_projected_columns() {
+        // symbols of "b" are invalid, but never read when only "a" is requested
+        let xml = header_xml(&[int_field("a", 0, 10, 0, 1), int_field("b", 10, 2, 1, 1)], 1, 3, 12, 3);
+        let bytes = qvd_bytes(&xml, &[&TWO_INTS[..], &[0x03, 0x03, 1, 0, 1]].concat());
+
+        let mut reader = io::Cursor::new(&bytes);
+        let (_, columns) = read_columns_from_reader(&mut reader, &["a"], &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns.len(), 1);
+        assert_eq!(columns[0].as_values(), vec![&CellValue::Int(20), &CellValue::Int(10), &CellValue::Int(20)]);
+
+        let err = read_columns_from_reader(&mut io::Cursor::new(&bytes), &["b"], &QvdReadOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::InvalidSymbol { offset: 0 });
+        let err = read_columns_from_reader(&mut io::Cursor::new(&bytes), &["c"], &QvdReadOptions::default()).unwrap_err();
+        assert!(matches!(err.kind(), QvdErrorKind::ColumnNotFound { .. }));
+
+        let (_, columns) = read_qvd_columns("tests/test_file.qvd", &["some_null", "all_int"], &QvdReadOptions::default()).unwrap();
+        let (_, all) = read_qvd("tests/test_file.qvd", &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns[0], all[3]);
+        assert_eq!(columns[1], all[0]);
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let read = |xml: &str, data: &[u8]| {
+            let bytes = qvd_bytes(xml, data);
+            read_qvd_from_reader(&mut bytes.as_slice(), &QvdReadOptions::default()).unwrap_err().kind().clone()
+        };
+        let rows = [1, 0, 1];
+        let data = [&TWO_INTS[..], &rows].concat();
+        let xml = |field: QvdFieldHeader, no_of_records| header_xml(&[field], 1, no_of_records, 10, 3);
+
+        assert_eq!(read("<QvdTableHeader><Fields>", &data), QvdErrorKind::InvalidHeader);
+        assert_eq!(read(&xml(int_field("num", 0, 10, 0, 1), 4), &data), QvdErrorKind::Truncated);
+        assert_eq!(
+            read(&xml(int_field("num", 5, 10, 0, 1), 3), &data),
+            QvdErrorKind::FieldOutOfBounds { field: "num".into() },
+        );
+        assert_eq!(
+            read(&xml(int_field("num", 0, 10, 4, 5), 3), &data),
+            QvdErrorKind::InvalidBitLayout { field: "num".into() },
+        );
+        // The symbol tables must end where the rows start
+        assert_eq!(read(&xml(int_field("num", 0, 8, 0, 1), 3), &data), QvdErrorKind::InvalidFormat);
+        let truncated = [&TWO_INTS[..8], &rows].concat();
+        let truncated_xml = header_xml(&[int_field("num", 0, 8, 0, 1)], 1, 3, 8, 3);
+        assert_eq!(read(&truncated_xml, &truncated), QvdErrorKind::InvalidSymbol { offset: 5 });
+        let bad_type = [&[0x03][..], &TWO_INTS[1..], &rows].concat();
+        assert_eq!(read(&xml(int_field("num", 0, 10, 0, 1), 3), &bad_type), QvdErrorKind::InvalidSymbol { offset: 0 });
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_spans() {
+        use std::sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex};
+        use tracing::{span, Event, Metadata, Subscriber};
+
+        type RecordedSpan = (&'static str, Vec<String>);
+
+        // Records the name and declared fields of every span
+        #[derive(Default, Clone)]
+        struct SpanRecorder {
+            next_id: Arc<AtomicU64>,
+            spans: Arc<Mutex<Vec<RecordedSpan>>>,
+        }
+
+        impl Subscriber for SpanRecorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+            fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+                let fields = attrs.metadata().fields().iter().map(|f| f.name().to_string()).collect();
+                self.spans.lock().unwrap().push((attrs.metadata().name(), fields));
+                span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let recorder = SpanRecorder::default();
+        let dispatch = tracing::Dispatch::new(recorder.clone());
+        // rayon workers need the recorder as their default too, a global default
+        // would also pick up spans of tests running concurrently
+        let worker_dispatch = dispatch.clone();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .start_handler(move |_| std::mem::forget(tracing::dispatcher::set_default(&worker_dispatch)))
+            .build()
+            .unwrap();
+        tracing::dispatcher::with_default(&dispatch, || pool.install(|| read_qvd("tests/test_file.qvd", &QvdReadOptions::default()))).unwrap();
+
+        let spans = recorder.spans.lock().unwrap();
+        let count = |name| spans.iter().filter(|(n, _)| *n == name).count();
+        assert_eq!(count("read_qvd"), 1);
+        assert_eq!(count("parse_xml"), 1);
+        assert_eq!(count("decode_symbols"), 5);
+        assert_eq!(count("decode_indexes"), 5);
+        let (_, fields) = spans.iter().find(|(n, _)| *n == "decode_symbols").unwrap();
+        assert_eq!(fields, &["field", "bytes", "symbols"]);
+    }
+
     #[test]
     fn test_double() {
         let buf: Vec<u8> = vec![
             0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x7a, 0x40, 0x02, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x50, 0x7a, 0x40,
         ];
-        let res = get_column_values_from_buf(&buf);
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
         let expected = vec![CellValue::Float(420.0), CellValue::Float(421.0)];
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn test_non_finite_double() {
+        let mut buf = vec![0x02];
+        buf.extend_from_slice(&f64::NAN.to_le_bytes());
+        buf.push(0x02);
+        buf.extend_from_slice(&f64::INFINITY.to_le_bytes());
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
+        assert!(matches!(res[0], CellValue::Float(f) if f.is_nan()));
+        assert_eq!(res[1], CellValue::Float(f64::INFINITY));
+
+        let strict = QvdReadOptions { reject_non_finite_floats: true, ..Default::default() };
+        let err = get_column_values_from_buf(&buf, &strict).unwrap_err();
+        assert!(matches!(err.kind(), QvdErrorKind::InvalidValue));
+    }
+
+    #[test]
+    fn test_coerce_integral_floats() {
+        let buf: Vec<u8> = [2., 2.5, -7., 3e10].iter().flat_map(|f: &f64| [&[0x02][..], &f.to_le_bytes()].concat()).collect();
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
+        assert_eq!(res[0], CellValue::Float(2.));
+
+        let options = QvdReadOptions { coerce_integral_floats: true, ..Default::default() };
+        let res = get_column_values_from_buf(&buf, &options).unwrap();
+        assert_eq!(res, vec![CellValue::Int(2), CellValue::Float(2.5), CellValue::Int(-7), CellValue::Float(3e10)]);
+    }
+
+    #[test]
+    fn test_unsigned_ints() {
+        let buf: Vec<u8> = vec![0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x05, 0x00, 0x00, 0x00];
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
+        assert_eq!(res, vec![CellValue::Int(-1), CellValue::Int(5)]);
+
+        let options = QvdReadOptions { unsigned_ints: true, ..Default::default() };
+        let res = get_column_values_from_buf(&buf, &options).unwrap();
+        assert_eq!(res, vec![CellValue::Float(4294967295.), CellValue::Int(5)]);
+    }
+
+    #[test]
+    fn test_non_finite_text_stays_text() {
+        let buf: Vec<u8> = [&[4], &b"NaN"[..], &[0, 4], &b"inf"[..], &[0]].concat();
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
+        assert_eq!(res, vec![CellValue::Text("NaN".into()), CellValue::Text("inf".into())]);
+    }
+
     #[test]
     fn test_int() {
         let buf: Vec<u8> = vec![0x01, 0x0A, 0x00, 0x00, 0x00, 0x01, 0x14, 0x00, 0x00, 0x00];
-        let res = get_column_values_from_buf(&buf);
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
         let expected = vec![CellValue::Int(10), CellValue::Int(20)];
         assert_eq!(expected, res);
     }
 
+    // Mixed symbols spanning more than PARALLEL_SYMBOLS_MIN_BYTES
+    fn high_cardinality_symbols() -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut i = 0i32;
+        while buf.len() < 2 * PARALLEL_SYMBOLS_MIN_BYTES {
+            match i % 4 {
+                0 => { buf.push(1); buf.extend_from_slice(&i.to_le_bytes()); }
+                1 => { buf.push(2); buf.extend_from_slice(&(i as f64 / 3.).to_le_bytes()); }
+                2 => { buf.push(4); buf.extend_from_slice(format!("value {i}").as_bytes()); buf.push(0); }
+                _ => { buf.push(5); buf.extend_from_slice(&i.to_le_bytes()); buf.extend_from_slice(format!("{i:08}").as_bytes()); buf.push(0); }
+            }
+            i += 1;
+        }
+        buf
+    }
+
+    #[test]
+    fn test_parallel_symbol_decode() {
+        let buf = high_cardinality_symbols();
+        let options = QvdReadOptions::default();
+        let serial: Vec<CellValue> = decode_symbols(&buf, &options).unwrap().into_iter().map(CellValue::from).collect();
+        assert_eq!(decode_symbols_par(&buf, &options).unwrap(), serial);
+        assert_eq!(get_column_values_from_buf(&buf, &options).unwrap(), serial);
+
+        let mut truncated = buf.clone();
+        truncated.push(4);
+        truncated.extend_from_slice(b"open");
+        assert!(matches!(decode_symbols_par(&truncated, &options).unwrap_err().kind(), QvdErrorKind::InvalidSymbol { .. }));
+    }
+
+    #[test]
+    fn test_check_utf8() {
+        // tests/invalid_utf8.qvd is tests/test_file.qvd with the symbols Q1 and Q3 corrupted
+        let problems = check_utf8(&std::fs::read("tests/invalid_utf8.qvd").unwrap()).unwrap();
+        assert_eq!(problems, vec![
+            Utf8Problem { column: "all_string".into(), symbol_index: 0, byte_offset: 3611 },
+            Utf8Problem { column: "all_string".into(), symbol_index: 2, byte_offset: 3620 },
+        ]);
+        assert!(check_utf8(&std::fs::read("tests/test_file.qvd").unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reject_invalid_utf8() {
+        let (_, columns) = read_qvd("tests/invalid_utf8.qvd", &QvdReadOptions::default()).unwrap();
+        assert_eq!(columns[1].symbols[0], CellValue::Null);
+
+        let strict = QvdReadOptions { reject_invalid_utf8: true, ..Default::default() };
+        let expected = QvdErrorKind::InvalidUtf8 { column: "all_string".into(), symbol_index: 0, offset: 1 };
+        assert_eq!(read_qvd("tests/invalid_utf8.qvd", &strict).unwrap_err().kind(), &expected);
+        let err = read_qvd_columns("tests/invalid_utf8.qvd", &["all_string"], &strict).unwrap_err();
+        assert_eq!(err.kind(), &expected);
+        let bytes = std::fs::read("tests/invalid_utf8.qvd").unwrap();
+        assert_eq!(read_qvd_ref(&bytes, &strict).unwrap_err().kind(), &expected);
+        assert!(read_qvd("tests/test_file.qvd", &strict).is_ok());
+    }
+
+    #[test]
+    fn test_header_with_bom() {
+        let file = std::fs::read("tests/test_file.qvd").unwrap();
+        let mut bytes = b"\xEF\xBB\xBF \r\n".to_vec();
+        bytes.extend_from_slice(&file);
+        let doc = QvdDocument::from_bytes(&bytes).unwrap();
+        assert_eq!(doc.columns(), QvdDocument::from_bytes(&file).unwrap().columns());
+    }
+
+    #[test]
+    fn test_offset_past_symbol_tables() {
+        let file = std::fs::read("tests/test_file.qvd").unwrap();
+        let table_offset = file.windows(20).rposition(|w| w == b"<Offset>237</Offset>").unwrap();
+        let mut bytes = file.clone();
+        bytes[table_offset..table_offset + 20].copy_from_slice(b"<Offset>261</Offset>");
+        let err = QvdDocument::from_bytes(&bytes).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::InvalidFormat);
+        assert_eq!(err.message(), "symbol tables end at byte 237 but the header declares offset 261");
+    }
+
     #[test]
     #[rustfmt::skip]
     fn test_mixed_numbers() {
@@ -214,7 +1472,7 @@ mod tests {
             0x05, 0x00, 0x00, 0x00, 0x00, 0x37, 0x30, 0x30, 0x30, 0x00,
             0x06, 0x00,0x00,0x00, 0x00,0x00,0x00,0x00,0x00, 0x38, 0x36, 0x35, 0x2e, 0x32, 0x00
         ];
-        let res = get_column_values_from_buf(&buf);
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
         let expected = vec![
             CellValue::Float(420.),
             CellValue::Float(421.),
@@ -224,6 +1482,11 @@ mod tests {
             CellValue::Float(865.2)
         ];
         assert_eq!(expected, res);
+
+        let column = Column { header: Header("mixed".into()), symbols: res, indexes: vec![] };
+        let histogram = column.type_histogram();
+        assert_eq!(histogram.len(), 2);
+        assert_eq!((histogram["float"], histogram["int"]), (3, 3));
     }
 
     #[test]
@@ -232,7 +1495,7 @@ mod tests {
             4, 101, 120, 97, 109, 112, 108, 101, 32, 116, 101, 120, 116, 0, 4, 114, 117, 115, 116,
             0,
         ];
-        let res = get_column_values_from_buf(&buf);
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
         let expected = vec![CellValue::Text("example text".into()), CellValue::Text("rust".into())];
         assert_eq!(expected, res);
     }
@@ -246,7 +1509,7 @@ mod tests {
             0x04, 0xF0, 0x9F, 0x90, 0x8D, 0xF0, 0x9F, 0xA6, 0x80, 0x00,
             0x04, 0x54, 0x72, 0xC3, 0xA4, 0x67, 0x65, 0x72, 0x00,
         ];
-        let res = get_column_values_from_buf(&buf);
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
         let expected = vec![CellValue::Text("也有中文简体字".into()), CellValue::Text("🐍🦀".into()), CellValue::Text("Träger".into())];
         assert_eq!(expected, res);
     }
@@ -262,12 +1525,12 @@ mod tests {
             4, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 0
 
         ];
-        let res = get_column_values_from_buf(&buf);
+        let res = get_column_values_from_buf(&buf, &QvdReadOptions::default()).unwrap();
         let expected = vec![
             CellValue::Text("example text".into()),
             CellValue::Text("rust".into()),
             CellValue::Int(1234),
-            CellValue::Text("double".into()),
+            CellValue::Dual(f64::from_le_bytes([1; 8]), "double".into()),
             CellValue::Int(1111111111),
             CellValue::Float(1111111111111111.),
             CellValue::Text("11111111111111111".into()),
@@ -287,6 +1550,7 @@ mod tests {
             bit_offset: 10,
             bit_width: 3,
             bias: 0,
+            tags: Vec::new(),
         };
         let record_byte_size = buf.len();
         let res = get_row_indexes(&buf, &field, record_byte_size);
@@ -294,9 +1558,24 @@ mod tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn test_zero_bit_width() {
+        let buf: Vec<u8> = vec![0xFF, 0xFF, 0x12, 0x34];
+        let mut field = int_field("single", 0, 0, 16, 0);
+        assert_eq!(get_row_indexes(&buf, &field, 2), vec![0, 0]);
+        field.bias = -2;
+        assert_eq!(get_row_indexes(&buf, &field, 2), vec![-2, -2]);
+        field.bit_offset = 0;
+        assert_eq!(field_indexes(&buf, &field, 2, 2).unwrap(), vec![-2, -2]);
+
+        let (_, columns) = read_qvd("tests/test_file.qvd", &QvdReadOptions::default()).unwrap();
+        let all_null = columns.iter().find(|col| col.header.0 == "all Null").unwrap();
+        assert_eq!(all_null.indexes, vec![-2; 12]);
+    }
+
     #[test]
     fn read_test_file_qvd_null_parallel() {
-        let result = read_qvd("tests/test_file.qvd").unwrap();
+        let (_, result) = read_qvd("tests/test_file.qvd", &QvdReadOptions::default()).unwrap();
 
         let mut expected: Vec<Column> = Vec::new();
 
@@ -368,7 +1647,7 @@ mod tests {
     #[test]
     fn read_test_file_columns_parallel() {        
         let now = Instant::now();
-        let result = read_qvd("tests/big_file.qvd").unwrap();
+        let (_, result) = read_qvd("tests/big_file.qvd", &QvdReadOptions::default()).unwrap();
         let duration = Instant::now().checked_duration_since(now).unwrap();
         println!("Duration reading: {duration:?}");
 
@@ -378,13 +1657,13 @@ mod tests {
 
     #[test]
     fn read_int_file() {        
-        let result = read_qvd("tests/ints.qvd").unwrap();
+        let (_, result) = read_qvd("tests/ints.qvd", &QvdReadOptions::default()).unwrap();
         assert_eq!(result.into_iter().next().unwrap().into_values(), vec![CellValue::Int(1), 2.into(), 3.into()] );
     }
 
     #[test]
     fn read_floats_file() {        
-        let result = read_qvd("tests/floats.qvd").unwrap();
+        let (_, result) = read_qvd("tests/floats.qvd", &QvdReadOptions::default()).unwrap();
         let vec_of_values: Vec<_> = result.into_iter().map(|col| col.into_values()).collect();
         assert_eq!(vec_of_values, vec![ vec![CellValue::Int(1), 2.into(), 3.into()], vec![CellValue::Float(1.1), 2.1.into(), 3.1.into()]]);
     }
@@ -0,0 +1,23 @@
+use std::path::{Path, PathBuf};
+
+/// A file in the temp directory, removed when dropped so that a failing
+/// assertion doesn't leave it behind.
+pub(crate) struct TempFile(PathBuf);
+
+impl TempFile {
+    pub(crate) fn new(name: &str, contents: &[u8]) -> Self {
+        let path = std::env::temp_dir().join(format!("rqvd_{name}_{}.qvd", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        TempFile(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
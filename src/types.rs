@@ -1,34 +1,342 @@
-use std::{fmt::Display, path::Path};
+use std::{cmp::Ordering, collections::{BTreeMap, HashMap}, fmt::Display, hash::{DefaultHasher, Hash, Hasher}, io::{BufRead, BufReader, Read}, path::Path};
 
 
-use crate::{error::QvdError, reader::read_qvd};
+use crate::{error::{QvdError, QvdErrorKind}, export::FloatFormat, qvd_structure::QvdTableHeader, reader::{check_utf8, metadata, parse_number, read_header, read_qvd, read_qvd_at, read_qvd_chunked, read_qvd_columns, read_qvd_dictionaries, read_qvd_from_reader, read_qvd_parts, read_qvd_sample, read_qvd_ref, read_raw_header, QvdReadOptions, Utf8Problem}};
 
+use indexmap::IndexMap;
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 #[cfg(test)]
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::IntoParallelRefIterator;
 
 #[derive(Debug)]
 pub struct QvdDocument {
+    metadata: QvdMetadata,
     columns: Vec<Column>,
 }
 
+/// Table level values from the header of the file a document was read from.
+/// Documents assembled in memory (e.g. by `concat` or `inner_join`) carry
+/// default metadata.
+#[derive(Debug, Clone, Default)]
+pub struct QvdMetadata {
+    pub(crate) table_name: String,
+    pub(crate) creator_doc: String,
+    pub(crate) no_of_records: usize,
+    pub(crate) record_byte_size: usize,
+    pub(crate) offset: usize,
+    pub(crate) length: usize,
+    pub(crate) data_section_len: usize,
+    pub(crate) field_tags: IndexMap<String, Vec<String>>,
+}
+
+impl QvdMetadata {
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn creator_doc(&self) -> &str {
+        &self.creator_doc
+    }
+
+    pub fn no_of_records(&self) -> usize {
+        self.no_of_records
+    }
+
+    /// Size in bytes of one bit-stuffed record in the row section.
+    pub fn record_byte_size(&self) -> usize {
+        self.record_byte_size
+    }
+
+    /// Byte offset of the row section, relative to the start of the symbol
+    /// tables (i.e. the total size of the symbol tables).
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Declared length in bytes of the row section.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Bytes actually following the symbol tables in the file. For an intact
+    /// file this is `no_of_records * record_byte_size`, anything else points
+    /// to a truncated or corrupt row section.
+    pub fn data_section_len(&self) -> usize {
+        self.data_section_len
+    }
+
+    /// Tags the header declares for `field`, e.g. `$key` or `$numeric`. Empty
+    /// for unknown fields.
+    pub fn field_tags(&self, field: &str) -> &[String] {
+        self.field_tags.get(field).map_or(&[], Vec::as_slice)
+    }
+}
+
 impl QvdDocument {
     pub fn read(path: impl AsRef<Path>) -> Result<Self, QvdError> {
-        let columns = read_qvd(path.as_ref())?;
-        Ok(Self { columns })
+        Self::read_with_options(path, &QvdReadOptions::default())
+    }
+
+    pub fn read_with_options(path: impl AsRef<Path>, options: &QvdReadOptions) -> Result<Self, QvdError> {
+        let (metadata, columns) = read_qvd(path.as_ref(), options)?;
+        Ok(Self { metadata, columns })
+    }
+
+    /// Reads the file at `path` and checks that its columns have exactly the
+    /// expected names, order and `Column::dtype`. A mismatch is reported as a
+    /// `SchemaMismatch` error listing every difference.
+    pub fn read_expecting(path: impl AsRef<Path>, expected: &[(&str, ColumnType)]) -> Result<Self, QvdError> {
+        let doc = Self::read(path)?;
+        let differences = doc.schema_diff_with(expected.iter().copied());
+        if !differences.is_empty() {
+            return Err(QvdError::new(
+                QvdErrorKind::SchemaMismatch,
+                format!("schema differs from expected: {differences:?}"),
+            ));
+        }
+        Ok(doc)
+    }
+
+    /// Reads a .qvd file embedded in a larger container, e.g. after an index,
+    /// treating everything from `start_offset` on as the .qvd file.
+    pub fn read_at(path: impl AsRef<Path>, start_offset: u64) -> Result<Self, QvdError> {
+        let (metadata, columns) = read_qvd_at(path, start_offset, &QvdReadOptions::default())?;
+        Ok(Self { metadata, columns })
+    }
+
+    /// Scans every text symbol of the file at `path` and reports each one that
+    /// isn't valid UTF-8, instead of decoding it as null. An empty list means
+    /// all text is valid.
+    pub fn check_utf8(path: impl AsRef<Path>) -> Result<Vec<Utf8Problem>, QvdError> {
+        check_utf8(&std::fs::read(path)?)
+    }
+
+    /// Reads only the given columns, in the order given. The symbol tables of
+    /// the other columns are skipped without being read.
+    pub fn read_columns(path: impl AsRef<Path>, columns: &[&str]) -> Result<Self, QvdError> {
+        let (metadata, columns) = read_qvd_columns(path, columns, &QvdReadOptions::default())?;
+        Ok(Self { metadata, columns })
+    }
+
+    /// A uniform random sample of `n` rows of the file at `path`, in file
+    /// order, or all rows if it has fewer. The row section is streamed and
+    /// only the sampled records are kept, so memory doesn't grow with the
+    /// file; the symbol tables are still read in full. The same `seed` gives
+    /// the same sample.
+    pub fn read_sample(path: impl AsRef<Path>, n: usize, seed: u64) -> Result<Self, QvdError> {
+        let (metadata, columns) = read_qvd_sample(path, n, seed, &QvdReadOptions::default())?;
+        Ok(Self { metadata, columns })
+    }
+
+    /// The distinct values of every column, in file order, as stored in the
+    /// symbol tables. Row indexes are never read, so this is much cheaper
+    /// than `read` when only the dictionaries are needed.
+    pub fn read_dictionaries(path: impl AsRef<Path>) -> Result<Vec<(String, Vec<CellValue>)>, QvdError> {
+        read_qvd_dictionaries(path, &QvdReadOptions::default())
+    }
+
+    /// The XML header of the file at `path` exactly as embedded, without
+    /// deserializing it. Useful when a header fails to parse.
+    pub fn raw_header_xml(path: impl AsRef<Path>) -> Result<String, QvdError> {
+        read_raw_header(path)
+    }
+
+    /// Reads a document from any reader. Malformed or truncated input is
+    /// reported as an error, decoding never panics.
+    pub fn try_read(reader: impl Read) -> Result<Self, QvdError> {
+        let (metadata, columns) = read_qvd_from_reader(&mut BufReader::new(reader), &QvdReadOptions::default())?;
+        Ok(Self { metadata, columns })
+    }
+
+    /// Reads only the header, up to and including its null terminator, and
+    /// leaves `reader` at the start of the data section, so the caller can
+    /// decide whether to go on reading. The rows haven't been seen yet, so
+    /// `data_section_len` is the `length` the header declares.
+    pub fn read_schema_from_reader<R: BufRead>(reader: &mut R) -> Result<QvdMetadata, QvdError> {
+        let header = Self::read_header_from_reader(reader)?;
+        Ok(metadata(&header, header.length))
+    }
+
+    /// Like `read_schema_from_reader`, but returns the parsed header with its
+    /// field layout. To go on reading, pass it and the rest of the stream to
+    /// `from_parts`.
+    pub fn read_header_from_reader<R: BufRead>(reader: &mut R) -> Result<QvdTableHeader, QvdError> {
+        read_header(reader, &mut Vec::new())
+    }
+
+    /// Decodes a .qvd file already held in memory.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, QvdError> {
+        let (metadata, columns) = read_qvd_from_reader(&mut &bytes[..], &QvdReadOptions::default())?;
+        Ok(Self { metadata, columns })
+    }
+
+    /// Streams a file in chunks of `chunk_size` rows without building a
+    /// document: symbol tables are decoded once, then `f` is called with the
+    /// resolved rows of each chunk in turn. A `chunk_size` of 0 is treated as 1.
+    pub fn process_chunked<F>(path: impl AsRef<Path>, chunk_size: usize, f: F) -> Result<(), QvdError>
+    where
+        F: FnMut(&[Vec<&CellValue>]),
+    {
+        read_qvd_chunked(path, chunk_size, &QvdReadOptions::default(), f)
+    }
+
+    /// Decodes `data`, the symbol tables and rows following the header in a
+    /// .qvd file, using a header parsed or cached separately.
+    pub fn from_parts(header: QvdTableHeader, data: &[u8]) -> Result<Self, QvdError> {
+        let (metadata, columns) = read_qvd_parts(&header, data, &QvdReadOptions::default())?;
+        Ok(Self::from_metadata(metadata, columns))
+    }
+
+    pub(crate) fn from_metadata(metadata: QvdMetadata, columns: Vec<Column>) -> Self {
+        Self { metadata, columns }
+    }
+
+    pub(crate) fn from_columns(columns: Vec<Column>) -> Self {
+        Self { metadata: QvdMetadata::default(), columns }
+    }
+
+    pub fn metadata(&self) -> &QvdMetadata {
+        &self.metadata
+    }
+
+    /// Reads every file matching `pattern` (sorted by path) and concatenates them
+    /// into one document. All files must share the same columns.
+    pub fn read_glob(pattern: &str) -> Result<Self, QvdError> {
+        let mut paths = glob::glob(pattern)
+            .map_err(|err| QvdError::new(QvdErrorKind::ReadFile, format!("invalid pattern '{pattern}': {err}")))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| QvdError::new(QvdErrorKind::ReadFile, err.to_string()))?;
+        if paths.is_empty() {
+            return Err(QvdError::new(QvdErrorKind::ReadFile, format!("no files match '{pattern}'")));
+        }
+        paths.sort();
+        let documents = paths.iter()
+            .map(QvdDocument::read)
+            .collect::<Result<Vec<_>, _>>()?;
+        QvdDocument::concat(documents)
+    }
+
+    /// Appends the rows of all documents in order. The documents must have the
+    /// same column names in the same order.
+    pub fn concat(documents: Vec<QvdDocument>) -> Result<Self, QvdError> {
+        let mut documents = documents.into_iter();
+        let Some(mut result) = documents.next() else {
+            return Ok(Self::from_columns(Vec::new()));
+        };
+        for doc in documents {
+            let names: Vec<_> = result.columns.iter().map(|col| &col.header).collect();
+            let other_names: Vec<_> = doc.columns.iter().map(|col| &col.header).collect();
+            if names != other_names {
+                return Err(QvdError::new(
+                    QvdErrorKind::SchemaMismatch,
+                    format!("cannot concat columns {other_names:?} to {names:?}"),
+                ));
+            }
+            for (col, other) in result.columns.iter_mut().zip(doc.columns) {
+                col.append(other);
+            }
+            result.metadata = QvdMetadata::default();
+        }
+        Ok(result)
+    }
+
+    /// Whether both documents have the same column names in the same order,
+    /// with the same `Column::dtype`, see `schema_diff`.
+    pub fn schema_compatible(&self, other: &QvdDocument) -> bool {
+        self.schema_diff(other).is_empty()
+    }
+
+    /// Every difference between the column names, order and types of both
+    /// documents, compared position by position.
+    pub fn schema_diff(&self, other: &QvdDocument) -> Vec<SchemaDifference> {
+        self.schema_diff_with(other.columns.iter().map(|col| (col.header.0.as_str(), col.dtype())))
+    }
+
+    fn schema_diff_with<'b>(&self, other: impl ExactSizeIterator<Item = (&'b str, ColumnType)>) -> Vec<SchemaDifference> {
+        let mut differences = Vec::new();
+        if self.columns.len() != other.len() {
+            differences.push(SchemaDifference::ColumnCount { left: self.columns.len(), right: other.len() });
+        }
+        for (position, (left, (right_name, right_type))) in self.columns.iter().zip(other).enumerate() {
+            if left.header.0 != right_name {
+                differences.push(SchemaDifference::Name {
+                    position,
+                    left: left.header.0.clone(),
+                    right: right_name.to_string(),
+                });
+            }
+            let left_type = left.dtype();
+            if left_type != right_type {
+                differences.push(SchemaDifference::Type { position, left: left_type, right: right_type });
+            }
+        }
+        differences
+    }
+
+    /// Differences between two documents, e.g. a generated file and a golden
+    /// one. Rows are only compared if the schemas match, pairing them by
+    /// position; rows present in only one document count as differing.
+    pub fn diff(&self, other: &QvdDocument) -> DocumentDiff {
+        let schema = self.schema_diff(other);
+        if !schema.is_empty() {
+            return DocumentDiff { schema, rows: Vec::new() };
+        }
+        let rows = (0..self.row_count().max(other.row_count()))
+            .filter(|&row| {
+                row >= self.row_count() || row >= other.row_count() ||
+                    self.columns.iter().zip(&other.columns)
+                        .any(|(left, right)| !left.resolve_row(row).same_value(right.resolve_row(row)))
+            })
+            .collect();
+        DocumentDiff { schema, rows }
+    }
+
+    /// Places the columns of `other` after those of `self`, pairing rows by
+    /// position. Both documents must have the same number of rows and no
+    /// column name in common.
+    pub fn merge_columns(&self, other: &QvdDocument) -> Result<QvdDocument, QvdError> {
+        if self.row_count() != other.row_count() {
+            return Err(QvdError::new(
+                QvdErrorKind::SchemaMismatch,
+                format!("cannot merge {} rows with {} rows", other.row_count(), self.row_count()),
+            ));
+        }
+        if let Some(col) = other.columns.iter().find(|col| self.column(&col.header.0).is_some()) {
+            let name = &col.header.0;
+            return Err(QvdError::new(QvdErrorKind::DuplicateColumn { column: name.clone() }, format!("column '{name}' already exists")));
+        }
+        Ok(QvdDocument::from_columns(self.columns.iter().chain(&other.columns).cloned().collect()))
     }
 
+    /// Columns in the order they are stored in the file. Every export keys its
+    /// output in this order.
     pub fn columns(&self) -> &[Column] {
         &self.columns
     }
 
-    pub fn rows(&self) -> RowIter {
+    /// Mutable access to the columns for in-place transformations. Columns
+    /// should keep the same number of rows, rows missing from a shorter
+    /// column read as null.
+    pub fn columns_mut(&mut self) -> &mut [Column] {
+        &mut self.columns
+    }
+
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns.iter().map(|col| col.header.0.as_str()).collect()
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.columns.first().map_or(0, |col| col.indexes.len())
+    }
+
+    pub fn rows(&self) -> RowIter<'_> {
         let values: Vec<_> = self.columns()
             .iter()
             .map(|col| {
                 col.as_values()
             })
             .collect();
-        let rows_total = values[0].len();
+        let rows_total = values.first().map_or(0, Vec::len);
         RowIter {
             values,
             rows_total,
@@ -36,15 +344,62 @@ impl QvdDocument {
         }
     }
 
+    /// Rows keyed by column name, keys are in column order.
+    pub fn rows_named(&self) -> impl Iterator<Item = IndexMap<&str, &CellValue>> + '_ {
+        let names = self.column_names();
+        self.rows().map(move |row| names.iter().copied().zip(row).collect())
+    }
+
+    /// Like `rows`, but every row owns its values.
+    pub fn rows_owned(&self) -> impl Iterator<Item = Vec<CellValue>> + '_ {
+        self.rows().map(|row| row.into_iter().cloned().collect())
+    }
+
+    /// Rows in batches of up to `size`, the last batch holding the remaining
+    /// rows. A `size` of 0 is treated as 1.
+    pub fn rows_chunked(&self, size: usize) -> impl Iterator<Item = Vec<Vec<&CellValue>>> + '_ {
+        let size = size.max(1);
+        let mut rows = self.rows();
+        std::iter::from_fn(move || {
+            let chunk: Vec<_> = rows.by_ref().take(size).collect();
+            (!chunk.is_empty()).then_some(chunk)
+        })
+    }
+
+    /// Every row as an owned map from column name to value, keys sorted by
+    /// name. See `to_records_ordered` to keep the column order.
+    pub fn to_records(&self) -> Vec<BTreeMap<String, CellValue>> {
+        self.rows_named()
+            .map(|row| row.into_iter().map(|(name, value)| (name.to_string(), value.clone())).collect())
+            .collect()
+    }
+
+    /// Like `to_records`, but keys are in column order.
+    pub fn to_records_ordered(&self) -> Vec<IndexMap<String, CellValue>> {
+        self.rows_named()
+            .map(|row| row.into_iter().map(|(name, value)| (name.to_string(), value.clone())).collect())
+            .collect()
+    }
+
+    /// Parallel iterator over owned rows, for CPU-bound per-row work. Rows are
+    /// resolved independently so rayon can split the work across threads.
+    pub fn par_rows(&self) -> impl IndexedParallelIterator<Item = Vec<CellValue>> + '_ {
+        (0..self.row_count()).into_par_iter().map(move |row| {
+            self.columns.iter()
+                .map(|col| col.resolve_row(row).clone())
+                .collect()
+        })
+    }
+
     #[cfg(test)]
-    pub fn rows_par(&self) -> RowIter {
+    pub fn rows_par(&self) -> RowIter<'_> {
         let values: Vec<_> = self.columns()
             .par_iter()
             .map(|col| {
                 col.as_values()
             })
             .collect();
-        let rows_total = values[0].len();
+        let rows_total = values.first().map_or(0, Vec::len);
         RowIter {
             values,
             rows_total,
@@ -53,21 +408,324 @@ impl QvdDocument {
     }
 
     #[cfg(test)]
-    pub fn rows_alt(&self) -> RowIterAlt {
+    pub fn rows_alt(&self) -> RowIterAlt<'_> {
         RowIterAlt {
             columns: self.columns(),
             index: 0,
         }
     }
 
-    pub fn find_row_indexes(&self, column_name: impl AsRef<str>, value: impl Into<CellValue>) -> Vec<usize> {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|col| col.header.0 == name)
+    }
+
+    /// The column at `index` in file order, `None` if out of range.
+    pub fn column_at(&self, index: usize) -> Option<&Column> {
+        self.columns.get(index)
+    }
+
+    /// Columns whose field carries `tag` in the file header, e.g. all `$key`
+    /// columns, in column order. See `QvdMetadata::field_tags`.
+    pub fn columns_by_tag(&self, tag: &str) -> Vec<&Column> {
         self.columns.iter()
-            .find(|col| col.header.0 == column_name.as_ref())
+            .filter(|col| self.metadata.field_tags(&col.header.0).iter().any(|t| t == tag))
+            .collect()
+    }
+
+    pub(crate) fn column_or_err(&self, name: &str) -> Result<&Column, QvdError> {
+        self.column(name).ok_or_else(|| column_not_found(name))
+    }
+
+    /// Appends a column computed from each row's values.
+    pub fn with_column<F: Fn(&[&CellValue]) -> CellValue>(&mut self, name: &str, f: F) -> Result<(), QvdError> {
+        if self.column(name).is_some() {
+            return Err(QvdError::new(QvdErrorKind::DuplicateColumn { column: name.into() }, format!("column '{name}' already exists")));
+        }
+        let values: Vec<_> = self.rows().map(|row| f(&row)).collect();
+        self.columns.push(Column::from_values(name, values));
+        Ok(())
+    }
+
+    /// Inner join on the column `on`, which must exist in both documents. Keys
+    /// are matched by value, nulls never match. The result holds the columns of
+    /// `self` followed by the non-key columns of `other`; names that collide get
+    /// a `_right` suffix.
+    pub fn inner_join(&self, other: &QvdDocument, on: &str) -> Result<QvdDocument, QvdError> {
+        let left_key = self.column_or_err(on)?;
+        let right_key = other.column_or_err(on)?;
+
+        let mut right_rows: HashMap<_, Vec<usize>> = HashMap::new();
+        for row in 0..right_key.indexes.len() {
+            let value = right_key.resolve_row(row);
+            if *value != CellValue::Null {
+                right_rows.entry(value.symbol_key()).or_default().push(row);
+            }
+        }
+
+        let (mut left_matches, mut right_matches) = (Vec::new(), Vec::new());
+        for row in 0..left_key.indexes.len() {
+            if let Some(matches) = right_rows.get(&left_key.resolve_row(row).symbol_key()) {
+                left_matches.extend(std::iter::repeat_n(row, matches.len()));
+                right_matches.extend(matches);
+            }
+        }
+
+        let mut columns: Vec<_> = self.columns.iter().map(|col| col.take(&left_matches)).collect();
+        for col in other.columns.iter().filter(|col| col.header.0 != on) {
+            let mut joined = col.take(&right_matches);
+            while columns.iter().any(|c| c.header == joined.header) {
+                joined.header = Header(format!("{}_right", joined.header.0));
+            }
+            columns.push(joined);
+        }
+        Ok(QvdDocument::from_columns(columns))
+    }
+
+    pub fn rename_column(&mut self, from: &str, to: &str) -> Result<(), QvdError> {
+        let position = self.columns.iter()
+            .position(|col| col.header.0 == from)
+            .ok_or_else(|| column_not_found(from))?;
+        if from != to && self.column(to).is_some() {
+            return Err(QvdError::new(QvdErrorKind::DuplicateColumn { column: to.into() }, format!("column '{to}' already exists")));
+        }
+        self.columns[position].header = Header(to.into());
+        Ok(())
+    }
+
+    /// Keeps only the columns named in `names`, in their current order. Names
+    /// without a column are ignored.
+    pub fn retain_columns(&mut self, names: &[&str]) {
+        self.columns.retain(|col| names.contains(&col.header.0.as_str()));
+    }
+
+    /// Removes the columns named in `names`, the others keep their order.
+    /// Names without a column are ignored.
+    pub fn drop_columns(&mut self, names: &[&str]) {
+        self.columns.retain(|col| !names.contains(&col.header.0.as_str()));
+    }
+
+    /// Converts every value of column `col` to `to`. Text is parsed like string
+    /// symbols are when reading, floats only convert to `Int` if they are whole
+    /// numbers in range. Nulls stay null. If any value can't be converted the
+    /// column is left unchanged and the error lists the offending values.
+    pub fn cast_column(&mut self, col: &str, to: ColumnType) -> Result<(), QvdError> {
+        let column = self.columns.iter_mut()
+            .find(|column| column.header.0 == col)
+            .ok_or_else(|| column_not_found(col))?;
+        let mut failed = Vec::new();
+        let symbols: Vec<_> = column.symbols.iter().map(|symbol| {
+            symbol.cast(to).unwrap_or_else(|| {
+                failed.push(format!("'{symbol}' ({})", symbol.type_name()));
+                CellValue::Null
+            })
+        }).collect();
+        if !failed.is_empty() {
+            return Err(QvdError::new(
+                QvdErrorKind::InvalidValue,
+                format!("cannot cast {} in column '{col}' to {to:?}", failed.join(", ")),
+            ));
+        }
+        column.symbols = symbols;
+        Ok(())
+    }
+
+    /// Replaces column `col` by a column `num_name` of its numbers and a column
+    /// `text_name` of its display text, in the same position. `Dual` values are
+    /// split into both parts, plain numbers render as text and plain text has
+    /// no number.
+    pub fn split_dual_column(&mut self, col: &str, num_name: &str, text_name: &str) -> Result<(), QvdError> {
+        let position = self.columns.iter()
+            .position(|column| column.header.0 == col)
+            .ok_or_else(|| column_not_found(col))?;
+        for name in [num_name, text_name] {
+            if (name != col && self.column(name).is_some()) || num_name == text_name {
+                return Err(QvdError::new(QvdErrorKind::DuplicateColumn { column: name.into() }, format!("column '{name}' already exists")));
+            }
+        }
+        let column = self.columns.remove(position);
+        let split = |name: &str, f: fn(&CellValue) -> CellValue| {
+            let mut part = Column {
+                header: Header(name.into()),
+                symbols: column.symbols.iter().map(f).collect(),
+                indexes: column.indexes.clone(),
+            };
+            part.rechunk();
+            part
+        };
+        let numbers = split(num_name, |value| match value {
+            CellValue::Dual(f, _) => CellValue::Float(*f),
+            CellValue::Int(_) | CellValue::Float(_) => value.clone(),
+            CellValue::Text(_) | CellValue::Null => CellValue::Null,
+        });
+        let texts = split(text_name, |value| match value {
+            CellValue::Null => CellValue::Null,
+            value => CellValue::Text(value.to_string()),
+        });
+        self.columns.splice(position..position, [numbers, texts]);
+        Ok(())
+    }
+
+    /// Drops symbols no row refers to any more and stores duplicate symbols
+    /// once, e.g. after filtering rows or casting a column. Row values are
+    /// unchanged.
+    pub fn rechunk(&mut self) {
+        self.columns.iter_mut().for_each(Column::rechunk);
+    }
+
+    pub fn find_row_indexes(&self, column_name: impl AsRef<str>, value: impl Into<CellValue>) -> Vec<usize> {
+        self.column(column_name.as_ref())
             .map(|col| col.find_row_indexes(value))
             .unwrap_or_default()
     }
 
-    pub fn rows_by_indexes<'a>(&'a self, row_indexes: &'a [usize]) -> RowIter {
+    /// Like `find_row_indexes`, but `Int` and `Float` values compare by number,
+    /// so querying `1` also finds rows holding `1.0`.
+    pub fn find_row_indexes_numeric(&self, column_name: impl AsRef<str>, value: impl Into<CellValue>) -> Vec<usize> {
+        self.column(column_name.as_ref())
+            .map(|col| col.find_row_indexes_numeric(value))
+            .unwrap_or_default()
+    }
+
+    /// Estimated heap and inline memory held by all columns, see
+    /// `Column::approx_size_bytes`.
+    pub fn approx_size_bytes(&self) -> usize {
+        self.columns.iter().map(Column::approx_size_bytes).sum()
+    }
+
+    /// Per-column summary: row counts, distinct values and, for `Int` and
+    /// `Float` columns, the minimum, maximum and mean.
+    pub fn describe(&self) -> Vec<ColumnStats> {
+        self.columns.iter().map(|col| {
+            let dtype = col.dtype();
+            let null_count = col.null_count();
+            let distinct = col.unique_referenced_values();
+            let (min, max, mean) = if dtype == ColumnType::Text {
+                (None, None, None)
+            } else {
+                let numbers = || distinct.iter().filter_map(|value| value.as_f64());
+                let sum: f64 = col.indexes.iter().filter_map(|&idx| col.resolve_index(idx).as_f64()).sum();
+                (
+                    numbers().min_by(f64::total_cmp),
+                    numbers().max_by(f64::total_cmp),
+                    (col.indexes.len() > null_count).then(|| sum / (col.indexes.len() - null_count) as f64),
+                )
+            };
+            ColumnStats {
+                name: col.header.0.clone(),
+                dtype,
+                count: col.indexes.len() - null_count,
+                null_count,
+                distinct_count: distinct.len(),
+                min,
+                max,
+                mean,
+            }
+        }).collect()
+    }
+
+    /// Smallest non-null value of a column by `CellValue::total_cmp`. Only the
+    /// symbol table is scanned, not every row.
+    pub fn column_min(&self, col: &str) -> Option<CellValue> {
+        self.column(col)?.non_null_symbols().min_by(|a, b| a.total_cmp(b)).cloned()
+    }
+
+    /// Largest non-null value of a column by `CellValue::total_cmp`.
+    pub fn column_max(&self, col: &str) -> Option<CellValue> {
+        self.column(col)?.non_null_symbols().max_by(|a, b| a.total_cmp(b)).cloned()
+    }
+
+    /// Sum of all numeric values of a column (see `CellValue::to_f64_lossy`),
+    /// counting every row. Nulls and non-numeric values are skipped.
+    pub fn column_sum(&self, col: &str) -> Result<f64, QvdError> {
+        self.column_sum_and_count(col).map(|(sum, _)| sum)
+    }
+
+    /// Mean of all numeric values of a column, see `column_sum`.
+    pub fn column_mean(&self, col: &str) -> Result<f64, QvdError> {
+        self.column_sum_and_count(col).map(|(sum, count)| sum / count as f64)
+    }
+
+    fn column_sum_and_count(&self, col: &str) -> Result<(f64, usize), QvdError> {
+        let column = self.column_or_err(col)?;
+        // Convert each symbol once, rows only reference them
+        let numbers: Vec<_> = column.symbols.iter().map(|symbol| symbol.to_f64_lossy()).collect();
+        let (sum, count) = column.indexes.iter()
+            .filter_map(|&idx| usize::try_from(idx).ok().and_then(|i| numbers.get(i).copied().flatten()))
+            .fold((0., 0), |(sum, count), value| (sum + value, count + 1));
+        if count == 0 {
+            return Err(QvdError::new(QvdErrorKind::NotNumeric, format!("column '{col}' has no numeric values")));
+        }
+        Ok((sum, count))
+    }
+
+    /// Compares column names and the rows as a multiset of resolved values, so
+    /// symbol table layout and row order don't matter.
+    pub fn equals_ignoring_order(&self, other: &QvdDocument) -> bool {
+        let names: Vec<_> = self.columns.iter().map(|col| &col.header).collect();
+        let other_names: Vec<_> = other.columns.iter().map(|col| &col.header).collect();
+        if names != other_names || self.row_count() != other.row_count() {
+            return false;
+        }
+        fn row_key(row: Vec<&CellValue>) -> Vec<SymbolKey<'_>> {
+            row.into_iter().map(CellValue::symbol_key).collect()
+        }
+        let mut counts: HashMap<_, isize> = HashMap::new();
+        for row in self.rows() {
+            *counts.entry(row_key(row)).or_default() += 1;
+        }
+        for row in other.rows() {
+            *counts.entry(row_key(row)).or_default() -= 1;
+        }
+        counts.values().all(|&count| count == 0)
+    }
+
+    /// Hash of the logical content: column names and the multiset of rows.
+    /// Independent of symbol table layout, row order and header metadata such
+    /// as creation times.
+    pub fn content_hash(&self) -> u64 {
+        let mut row_hashes: Vec<u64> = self.rows().map(|row| {
+            let mut hasher = DefaultHasher::new();
+            row.iter().for_each(|cell| cell.symbol_key().hash(&mut hasher));
+            hasher.finish()
+        }).collect();
+        row_hashes.sort_unstable();
+
+        let mut hasher = DefaultHasher::new();
+        self.columns.iter().for_each(|col| col.header.hash(&mut hasher));
+        row_hashes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Like `rows_by_indexes`, but fails with `QvdErrorKind::InvalidValue`
+    /// if any requested row is past the end of the document instead of
+    /// yielding it as nulls.
+    pub fn try_rows_by_indexes<'a>(&'a self, row_indexes: &'a [usize]) -> Result<RowIter<'a>, QvdError> {
+        let row_count = self.row_count();
+        if let Some(&row) = row_indexes.iter().find(|&&row| row >= row_count) {
+            return Err(QvdError::new(
+                QvdErrorKind::InvalidValue,
+                format!("row index {row} is out of range for {row_count} rows"),
+            ));
+        }
+        Ok(self.rows_by_indexes(row_indexes))
+    }
+
+    /// The columns `project` of the rows where `filter_col` holds `value`,
+    /// matched like `find_row_indexes`.
+    pub fn rows_where(&self, filter_col: &str, value: impl Into<CellValue>, project: &[&str]) -> Result<RowIter<'_>, QvdError> {
+        let row_indexes = self.column_or_err(filter_col)?.find_row_indexes(value);
+        let values = project.iter()
+            .map(|&name| self.column_or_err(name).map(|col| col.indexes_to_values(&row_indexes)))
+            .collect::<Result<_, _>>()?;
+        Ok(RowIter {
+            values,
+            rows_total: row_indexes.len(),
+            index: 0
+        })
+    }
+
+    /// Rows at `row_indexes`, rows past the end of the document are returned as nulls.
+    pub fn rows_by_indexes<'a>(&'a self, row_indexes: &'a [usize]) -> RowIter<'a> {
         let values: Vec<_> = self.columns()
             .iter()
             .map(|col| {
@@ -75,7 +733,7 @@ impl QvdDocument {
             })
             .collect();
 
-        let rows_total = values[0].len();
+        let rows_total = values.first().map_or(0, Vec::len);
         RowIter {
             values,
             rows_total,
@@ -84,19 +742,94 @@ impl QvdDocument {
     }
 }
 
+/// Assembles a document in memory from whole columns of values.
+#[derive(Debug, Default)]
+pub struct QvdDocumentBuilder {
+    columns: Vec<(String, Vec<CellValue>)>,
+}
+
+impl QvdDocumentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a column after those added before, one value per row.
+    pub fn add_column(mut self, name: impl Into<String>, values: Vec<CellValue>) -> Self {
+        self.columns.push((name.into(), values));
+        self
+    }
+
+    /// Builds the document, failing if the columns differ in length or two
+    /// share a name.
+    pub fn build(self) -> Result<QvdDocument, QvdError> {
+        let rows = self.columns.first().map_or(0, |(_, values)| values.len());
+        let mut columns: Vec<Column> = Vec::with_capacity(self.columns.len());
+        for (name, values) in self.columns {
+            if values.len() != rows {
+                return Err(QvdError::new(
+                    QvdErrorKind::SchemaMismatch,
+                    format!("column '{name}' has {} values, expected {rows}", values.len()),
+                ));
+            }
+            if columns.iter().any(|col| col.header.0 == name) {
+                return Err(QvdError::new(QvdErrorKind::DuplicateColumn { column: name.clone() }, format!("column '{name}' already exists")));
+            }
+            columns.push(Column::from_values(name, values));
+        }
+        Ok(QvdDocument::from_columns(columns))
+    }
+}
+
+impl<'a> IntoIterator for &'a QvdDocument {
+    type Item = Vec<&'a CellValue>;
+    type IntoIter = RowIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows()
+    }
+}
+
+/// `doc["Quarter"]` looks a column up by name, panicking if there is none.
+/// Use `QvdDocument::column` to handle missing columns.
+impl std::ops::Index<&str> for QvdDocument {
+    type Output = Column;
+
+    fn index(&self, name: &str) -> &Column {
+        self.column(name).unwrap_or_else(|| panic!("no column named '{name}'"))
+    }
+}
+
+/// `doc[0]` is the first column in file order, panicking if out of range.
+/// Use `QvdDocument::column_at` to handle missing columns.
+impl std::ops::Index<usize> for QvdDocument {
+    type Output = Column;
+
+    fn index(&self, index: usize) -> &Column {
+        self.column_at(index)
+            .unwrap_or_else(|| panic!("column index {index} out of range for {} columns", self.columns.len()))
+    }
+}
+
+pub(crate) fn column_not_found(name: &str) -> QvdError {
+    QvdError::new(QvdErrorKind::ColumnNotFound { column: name.into() }, format!("no column named '{name}'"))
+}
+
+/// Rows of a document, one value per column. The values borrow from the
+/// document, not from the iterator, so rows can be kept, collected or mapped
+/// with the standard adaptors, e.g. `doc.rows().map(|row| row[0].to_string())`.
 pub struct RowIter<'a> {
     values: Vec<Vec<&'a CellValue>>,
     rows_total: usize,
     index: usize
 }
 
-impl<'a, 'b: 'a> Iterator for RowIter<'a> {
+impl<'a> Iterator for RowIter<'a> {
     type Item = Vec<&'a CellValue>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.index < self.rows_total {
             let row: Vec<_> = self.values.iter()
-                .map(|col| *col.get(self.index).unwrap())
+                .map(|col| col.get(self.index).copied().unwrap_or(&CellValue::Null))
                 .collect();
             self.index += 1;
             Some(row)
@@ -105,8 +838,102 @@ impl<'a, 'b: 'a> Iterator for RowIter<'a> {
             None
         }
     }
+
+    // Skip rows by moving the cursor instead of materializing every skipped row
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.index = self.index.saturating_add(n).min(self.rows_total);
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.rows_total - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for RowIter<'_> {}
+
+impl<'a> RowIter<'a> {
+    /// Converts each row into a tuple such as `(i32, String, f64)`, one
+    /// element per column, each converted with `TryFrom<&CellValue>`.
+    pub fn typed<T: FromRow>(self) -> impl Iterator<Item = Result<T, QvdError>> + 'a {
+        self.map(|row| T::from_row(&row))
+    }
+}
+
+/// Read access shared by table backends, so code generic over `impl QvdTable`
+/// works with the eager `QvdDocument` as well as lazily resolved backends.
+pub trait QvdTable {
+    type Column;
+
+    fn row_count(&self) -> usize;
+
+    /// Column names in file order.
+    fn column_names(&self) -> Vec<&str>;
+
+    fn column(&self, name: &str) -> Option<&Self::Column>;
+
+    /// The value of column `col` in `row`, `None` if either doesn't exist.
+    fn cell(&self, row: usize, col: &str) -> Option<CellValue>;
+}
+
+impl QvdTable for QvdDocument {
+    type Column = Column;
+
+    fn row_count(&self) -> usize {
+        QvdDocument::row_count(self)
+    }
+
+    fn column_names(&self) -> Vec<&str> {
+        QvdDocument::column_names(self)
+    }
+
+    fn column(&self, name: &str) -> Option<&Column> {
+        QvdDocument::column(self, name)
+    }
+
+    fn cell(&self, row: usize, col: &str) -> Option<CellValue> {
+        QvdDocument::column(self, col)?.get(row)
+    }
 }
 
+/// Conversion of a row into a tuple of up to 12 elements, see `RowIter::typed`.
+pub trait FromRow: Sized {
+    fn from_row(row: &[&CellValue]) -> Result<Self, QvdError>;
+}
+
+macro_rules! impl_from_row {
+    ($len:literal => $($t:ident $i:tt),+) => {
+        impl<$($t),+> FromRow for ($($t,)+)
+        where
+            $($t: for<'v> TryFrom<&'v CellValue, Error = QvdError>,)+
+        {
+            fn from_row(row: &[&CellValue]) -> Result<Self, QvdError> {
+                if row.len() != $len {
+                    return Err(QvdError::new(
+                        QvdErrorKind::SchemaMismatch,
+                        format!("row has {} values but the tuple has {}", row.len(), $len),
+                    ));
+                }
+                Ok(($($t::try_from(row[$i])?,)+))
+            }
+        }
+    };
+}
+
+impl_from_row!(1 => A 0);
+impl_from_row!(2 => A 0, B 1);
+impl_from_row!(3 => A 0, B 1, C 2);
+impl_from_row!(4 => A 0, B 1, C 2, D 3);
+impl_from_row!(5 => A 0, B 1, C 2, D 3, E 4);
+impl_from_row!(6 => A 0, B 1, C 2, D 3, E 4, F 5);
+impl_from_row!(7 => A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_from_row!(8 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_from_row!(9 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_from_row!(10 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_from_row!(11 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_from_row!(12 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
 #[cfg(test)]
 pub struct RowIterAlt<'a> {
     columns: &'a [Column],
@@ -114,11 +941,11 @@ pub struct RowIterAlt<'a> {
 }
 
 #[cfg(test)]
-impl<'a, 'b: 'a> Iterator for RowIterAlt<'a> {
+impl<'a> Iterator for RowIterAlt<'a> {
     type Item = Vec<&'a CellValue>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.columns[0].indexes.len() {
+        if self.index < self.columns.first().map_or(0, |col| col.indexes.len()) {
             let row: Vec<_> = self.columns.iter()
                 .flat_map(|col| col.indexes_to_values(&[self.index]))
                 .collect();
@@ -130,7 +957,7 @@ impl<'a, 'b: 'a> Iterator for RowIterAlt<'a> {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Column {
     pub(crate) header: Header, 
     pub(crate) symbols: Vec<CellValue>,
@@ -139,65 +966,379 @@ pub struct Column {
 
 impl Column {
 
+    /// Builds a column from row values, storing each distinct value once in the
+    /// symbol table.
+    pub fn from_values(header: impl Into<Header>, values: Vec<CellValue>) -> Self {
+        let mut indexes = Vec::with_capacity(values.len());
+        let mut first_occurrence = vec![false; values.len()];
+        {
+            let mut lookup = HashMap::new();
+            for (row, value) in values.iter().enumerate() {
+                if *value == CellValue::Null {
+                    indexes.push(NULL_INDEX);
+                    continue;
+                }
+                let next = lookup.len() as isize;
+                let idx = *lookup.entry(value.symbol_key()).or_insert_with(|| {
+                    first_occurrence[row] = true;
+                    next
+                });
+                indexes.push(idx);
+            }
+        }
+        let symbols = values.into_iter()
+            .zip(first_occurrence)
+            .filter_map(|(value, first)| first.then_some(value))
+            .collect();
+        Self { header: header.into(), symbols, indexes }
+    }
+
     pub fn header(&self) -> Header {
         self.header.clone()
     }
 
-    pub fn as_values(&self) -> Vec<&CellValue> {
-        self.indexes.iter().map(|&idx| {
-            match idx {
-                i if i < 0 => { &CellValue::Null },
-                i => self.symbols.get(i as usize).unwrap(),
-            }
-        }).collect()
+    /// Copy of the given rows, sharing the same symbol table layout.
+    pub(crate) fn take(&self, row_indexes: &[usize]) -> Column {
+        Column {
+            header: self.header.clone(),
+            symbols: self.symbols.clone(),
+            indexes: row_indexes.iter()
+                .map(|&row| self.indexes.get(row).copied().unwrap_or(NULL_INDEX))
+                .collect(),
+        }
     }
 
-    pub fn into_values(self) -> Vec<CellValue> {
-        self.indexes.into_iter().map(|idx| {
-            match idx {
-                i if i < 0 => { CellValue::Null },
-                i => self.symbols.get(i as usize).unwrap().clone(),
-            }
-        }).collect()
+    /// Appends the rows of `other`, merging its symbols into this symbol table.
+    pub(crate) fn append(&mut self, other: Column) {
+        let symbols_len = self.symbols.len();
+        let mut new_symbols = Vec::new();
+        let remap: Vec<isize> = {
+            let mut lookup: HashMap<_, isize> = self.symbols.iter()
+                .enumerate()
+                .map(|(i, symbol)| (symbol.symbol_key(), i as isize))
+                .collect();
+            other.symbols.iter().map(|symbol| {
+                *lookup.entry(symbol.symbol_key()).or_insert_with(|| {
+                    new_symbols.push(symbol);
+                    (symbols_len + new_symbols.len() - 1) as isize
+                })
+            }).collect()
+        };
+        let new_symbols: Vec<_> = new_symbols.into_iter().cloned().collect();
+        self.symbols.extend(new_symbols);
+        self.indexes.extend(other.indexes.iter().map(|&idx| {
+            usize::try_from(idx).ok()
+                .and_then(|i| remap.get(i).copied())
+                .unwrap_or(NULL_INDEX)
+        }));
     }
 
-    // pub fn value_from_row_index(&self, row_index: usize) -> Option<CellValue> {
-    //     let row_index = self.indexes.get(row_index)?;
-    //     let value = match *row_index {
-    //         i if i < 0 => { CellValue::Null },
-    //         i => self.symbols.get(i as usize).unwrap().clone(),
-    //     };
-    //     Some(value)
-    // }
-
-    pub fn indexes_to_values(&self, row_indexes: &[usize]) -> Vec<&CellValue> {
-        row_indexes.iter().map(|&idx| {
-            match self.indexes.get(idx) {
-                Some(&i) if i < 0 => { &CellValue::Null },
-                Some(&i) => self.symbols.get(i as usize).unwrap(),
-                None => { &CellValue::Null }
+    /// Points every null row at `value`, adding it to the symbol table unless
+    /// it is already there. Replacing nulls with `Null` changes nothing.
+    pub fn replace_nulls(&mut self, value: CellValue) {
+        if value == CellValue::Null {
+            return;
+        }
+        let fill = match self.symbols.iter().position(|symbol| symbol.same_value(&value)) {
+            Some(i) => i as isize,
+            None => {
+                self.symbols.push(value);
+                self.symbols.len() as isize - 1
             }
-            
-        }).collect()
+        };
+        for i in 0..self.indexes.len() {
+            if *self.resolve_index(self.indexes[i]) == CellValue::Null {
+                self.indexes[i] = fill;
+            }
+        }
     }
 
-    pub fn find_row_indexes(&self, value: impl Into<CellValue>) -> Vec<usize> {
-        let cell_value = value.into();
-        let rows: Vec<_> = self.symbols.iter()
+    /// Replaces every value with `f(value)`. `f` runs once per distinct value
+    /// in the symbol table rather than once per row; nulls are left untouched.
+    pub fn map_values_in_place<F: FnMut(&CellValue) -> CellValue>(&mut self, mut f: F) {
+        for symbol in self.symbols.iter_mut().filter(|symbol| **symbol != CellValue::Null) {
+            *symbol = f(symbol);
+        }
+    }
+
+    /// Rebuilds the symbol table to hold each value referenced by a row exactly
+    /// once, in order of first reference, and renumbers the rows to match.
+    pub(crate) fn rechunk(&mut self) {
+        let symbols = &self.symbols;
+        let mut remap: Vec<Option<isize>> = vec![None; symbols.len()];
+        let mut kept = Vec::new();
+        let mut lookup = HashMap::new();
+        for idx in self.indexes.iter_mut() {
+            *idx = match usize::try_from(*idx).ok().filter(|&i| i < symbols.len()) {
+                Some(old) if symbols[old] != CellValue::Null => *remap[old].get_or_insert_with(|| {
+                    *lookup.entry(symbols[old].symbol_key()).or_insert_with(|| {
+                        kept.push(old);
+                        kept.len() as isize - 1
+                    })
+                }),
+                _ => NULL_INDEX,
+            };
+        }
+        let mut symbols = std::mem::take(&mut self.symbols);
+        self.symbols = kept.into_iter()
+            .map(|old| std::mem::replace(&mut symbols[old], CellValue::Null))
+            .collect();
+    }
+
+    /// Resolves a symbol index as stored for a row to its value. Stored indexes
+    /// already include the field's bias, so any index >= 0 addresses a symbol
+    /// even if the bias is negative. `NULL_INDEX` marks a null row. Other
+    /// negative indexes and indexes past the end of the symbol table only
+    /// occur in corrupt files; they also resolve to `Null` instead of
+    /// panicking, and are rejected when reading with
+    /// `QvdReadOptions::reject_out_of_range_indexes`.
+    pub fn resolve_index(&self, index: isize) -> &CellValue {
+        usize::try_from(index).ok()
+            .and_then(|i| self.symbols.get(i))
+            .unwrap_or(&CellValue::Null)
+    }
+
+    /// Whether any symbol of the column is a `Dual`, i.e. a number with its own
+    /// display text.
+    pub fn is_dual(&self) -> bool {
+        self.symbols.iter().any(|symbol| matches!(symbol, CellValue::Dual(..)))
+    }
+
+    /// Narrowest type holding every value: `Int` if all values are `Int`,
+    /// `Float` if all are numbers and `Text` otherwise, including for duals
+    /// and columns of only nulls. Decided on the symbol table.
+    pub fn dtype(&self) -> ColumnType {
+        let mut column_type = None;
+        for symbol in &self.symbols {
+            column_type = match (symbol, column_type) {
+                (CellValue::Null, current) => current,
+                (CellValue::Text(_) | CellValue::Dual(..), _) => return ColumnType::Text,
+                (CellValue::Int(_), None | Some(ColumnType::Int)) => Some(ColumnType::Int),
+                (CellValue::Int(_) | CellValue::Float(_), _) => Some(ColumnType::Float),
+            };
+        }
+        column_type.unwrap_or(ColumnType::Text)
+    }
+
+    /// Number of null rows.
+    pub fn null_count(&self) -> usize {
+        self.indexes.iter().filter(|&&idx| self.resolve_index(idx) == &CellValue::Null).count()
+    }
+
+    /// Number of distinct non-null values, see `unique_referenced_values`.
+    pub fn cardinality(&self) -> usize {
+        self.unique_referenced_values().len()
+    }
+
+    /// Estimated memory held by the column: the symbol table including the
+    /// text of every symbol, the row indexes and the name. Allocator overhead
+    /// is not counted.
+    pub fn approx_size_bytes(&self) -> usize {
+        let text: usize = self.symbols.iter().map(|symbol| match symbol {
+            CellValue::Text(s) | CellValue::Dual(_, s) => s.capacity(),
+            _ => 0,
+        }).sum();
+        std::mem::size_of::<Self>()
+            + self.header.0.capacity()
+            + self.symbols.capacity() * std::mem::size_of::<CellValue>()
+            + text
+            + self.indexes.capacity() * std::mem::size_of::<isize>()
+    }
+
+    /// Per-row validity, `true` where the row holds a value and `false` where it
+    /// resolves to null, including symbols that decoded as null.
+    pub fn null_mask(&self) -> Vec<bool> {
+        self.indexes.iter().map(|&idx| !matches!(self.resolve_index(idx), CellValue::Null)).collect()
+    }
+
+    pub fn as_values(&self) -> Vec<&CellValue> {
+        self.indexes.iter().map(|&idx| self.resolve_index(idx)).collect()
+    }
+
+    pub fn into_values(self) -> Vec<CellValue> {
+        self.indexes.iter().map(|&idx| self.resolve_index(idx).clone()).collect()
+    }
+
+    /// Value of the given row, or `None` if the column has fewer rows.
+    pub fn get(&self, row: usize) -> Option<CellValue> {
+        self.indexes.get(row).map(|&idx| self.resolve_index(idx).clone())
+    }
+
+    /// Row values rendered by `Display`, nulls as empty strings. A column of
+    /// only text symbols is copied straight from the symbol table.
+    pub fn to_string_vec(&self) -> Vec<String> {
+        if self.symbols.iter().all(|symbol| matches!(symbol, CellValue::Text(_))) {
+            self.indexes.iter().map(|&idx| match self.resolve_index(idx) {
+                CellValue::Text(s) => s.clone(),
+                _ => String::new(),
+            }).collect()
+        } else {
+            self.indexes.iter().map(|&idx| self.resolve_index(idx).to_string()).collect()
+        }
+    }
+
+    pub(crate) fn resolve_row(&self, row_index: usize) -> &CellValue {
+        match self.indexes.get(row_index) {
+            Some(&i) => self.resolve_index(i),
+            None => { &CellValue::Null }
+        }
+    }
+
+    pub fn indexes_to_values(&self, row_indexes: &[usize]) -> Vec<&CellValue> {
+        row_indexes.iter().map(|&idx| self.resolve_row(idx)).collect()
+    }
+
+    /// Row indexes whose value lies within `[low, high]` by `CellValue::total_cmp`,
+    /// so numeric text is compared as a number. Null rows are never included.
+    pub fn filter_between(&self, low: CellValue, high: CellValue) -> Vec<usize> {
+        let in_range: Vec<_> = self.symbols.iter().map(|symbol| {
+            *symbol != CellValue::Null
+                && symbol.total_cmp(&low) != Ordering::Less
+                && symbol.total_cmp(&high) != Ordering::Greater
+        }).collect();
+        self.indexes.iter()
             .enumerate()
-            .filter(|(_, elem)| **elem == cell_value)
-            .map(|(symbol_idx, _)| symbol_idx as isize)
+            .filter(|(_, &idx)| usize::try_from(idx).is_ok_and(|i| in_range.get(i) == Some(&true)))
+            .map(|(row, _)| row)
+            .collect()
+    }
+
+    /// Distinct non-null values referenced by at least one row, in symbol
+    /// table order. Symbols no row points at (e.g. after `take`) are skipped.
+    pub fn unique_referenced_values(&self) -> Vec<&CellValue> {
+        let mut referenced = vec![false; self.symbols.len()];
+        for &idx in &self.indexes {
+            if let Some(used) = usize::try_from(idx).ok().and_then(|i| referenced.get_mut(i)) {
+                *used = true;
+            }
+        }
+        self.symbols.iter()
+            .zip(referenced)
+            .filter(|(symbol, used)| *used && **symbol != CellValue::Null)
+            .map(|(symbol, _)| symbol)
+            .collect()
+    }
+
+    /// Symbols paired with their position in the symbol table, the index a row
+    /// refers to once the field's bias is applied (see `resolve_index`).
+    pub fn symbols_enumerated(&self) -> impl Iterator<Item = (usize, &CellValue)> {
+        self.symbols.iter().enumerate()
+    }
+
+    /// Distinct non-null symbols sorted by `CellValue::total_cmp`, e.g. for a
+    /// lookup table of a dimension.
+    pub fn sorted_symbols(&self) -> Vec<&CellValue> {
+        let mut symbols: Vec<_> = self.non_null_symbols().collect();
+        symbols.sort_by(|a, b| a.total_cmp(b));
+        symbols.dedup_by(|a, b| a.same_value(b));
+        symbols
+    }
+
+    /// Distinct values per row, how well dictionary encoding compresses the
+    /// column: close to 0 for few distinct values, 1 if every row is distinct.
+    /// A column without rows reports 0.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.indexes.is_empty() {
+            0.
+        } else {
+            self.symbols.len() as f64 / self.indexes.len() as f64
+        }
+    }
+
+    /// Number of symbols decoded as each `CellValue` variant, keyed by
+    /// `CellValue::type_name`. Symbols are counted once, not per row. Dual
+    /// symbols whose text is just the number are counted by the value they
+    /// decoded to, so a column of numbers stored as strings shows up as
+    /// `"int"` or `"float"`.
+    pub fn type_histogram(&self) -> HashMap<&'static str, usize> {
+        let mut histogram = HashMap::new();
+        for symbol in &self.symbols {
+            *histogram.entry(symbol.type_name()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Counts the numeric value of every row (see `CellValue::to_f64_lossy`) in
+    /// `bins` equally wide bins spanning the smallest to the largest value, as
+    /// `(bin_start, bin_end, count)`. The last bin includes the largest value.
+    /// Nulls, non-numeric and non-finite values are skipped.
+    pub fn histogram(&self, bins: usize) -> Result<Vec<(f64, f64, usize)>, QvdError> {
+        if bins == 0 {
+            return Err(QvdError::new(QvdErrorKind::InvalidValue, "a histogram needs at least one bin"));
+        }
+        // Convert each symbol once, rows only reference them
+        let numbers: Vec<_> = self.symbols.iter().map(|symbol| symbol.to_f64_lossy().filter(|f| f.is_finite())).collect();
+        let values: Vec<f64> = self.indexes.iter()
+            .filter_map(|&idx| usize::try_from(idx).ok().and_then(|i| numbers.get(i).copied().flatten()))
             .collect();
+        if values.is_empty() {
+            return Err(QvdError::new(QvdErrorKind::NotNumeric, format!("column '{}' has no numeric values", self.header.0)));
+        }
+        let (min, max) = values.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| (min.min(v), max.max(v)));
+        let width = (max - min) / bins as f64;
+        let mut counts = vec![0; bins];
+        for value in values {
+            let bin = if width > 0. { ((value - min) / width) as usize } else { 0 };
+            counts[bin.min(bins - 1)] += 1;
+        }
+        Ok(counts.into_iter().enumerate().map(|(bin, count)| {
+            let end = if bin == bins - 1 { max } else { min + width * (bin + 1) as f64 };
+            (min + width * bin as f64, end, count)
+        }).collect())
+    }
+
+    fn non_null_symbols(&self) -> impl Iterator<Item = &CellValue> {
+        self.symbols.iter().filter(|symbol| **symbol != CellValue::Null)
+    }
+
+    /// Row indexes holding `value`. Unlike `PartialEq`, a NaN query matches NaN
+    /// symbols, so NaNs decoded from a file can be found. Querying
+    /// `CellValue::Null` finds the null rows, see `find_null_row_indexes`.
+    pub fn find_row_indexes(&self, value: impl Into<CellValue>) -> Vec<usize> {
+        self.find_row_indexes_limited(value, usize::MAX)
+    }
+
+    /// The first `limit` row indexes holding `value`, see `find_row_indexes`.
+    /// Scanning stops once `limit` rows are found.
+    pub fn find_row_indexes_limited(&self, value: impl Into<CellValue>, limit: usize) -> Vec<usize> {
+        let cell_value = value.into();
+        self.rows_matching(|symbol| symbol.same_value(&cell_value), limit)
+    }
+
+    /// Like `find_row_indexes`, but `Int` and `Float` values compare by number.
+    pub fn find_row_indexes_numeric(&self, value: impl Into<CellValue>) -> Vec<usize> {
+        let cell_value = value.into();
+        self.rows_matching(|symbol| symbol.same_number(&cell_value), usize::MAX)
+    }
+
+    /// Row indexes of the null rows, i.e. rows whose index resolves to no
+    /// symbol.
+    pub fn find_null_row_indexes(&self) -> Vec<usize> {
+        self.find_row_indexes(CellValue::Null)
+    }
+
+    // Nulls aren't in the symbol table, so rows resolving to no symbol match
+    // if `matches` accepts `CellValue::Null`
+    fn rows_matching(&self, matches: impl Fn(&CellValue) -> bool, limit: usize) -> Vec<usize> {
+        let matching: Vec<bool> = self.symbols.iter().map(&matches).collect();
+        let null_matches = matches(&CellValue::Null);
 
         self.indexes.iter()
             .enumerate()
-            .filter(|(_, symbol_idx)| rows.contains(symbol_idx))
+            .filter(|(_, &symbol_idx)| {
+                usize::try_from(symbol_idx).ok().and_then(|i| matching.get(i)).copied().unwrap_or(null_matches)
+            })
             .map(|(idx, _)| idx)
+            .take(limit)
             .collect()
     }
 
 }
 
+/// Symbol index marking a null row. Files store it as a raw value of 0 with a
+/// bias of -2; any other negative index is invalid.
+pub const NULL_INDEX: isize = -2;
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
 pub struct Header(pub(crate) String);
 
@@ -207,20 +1348,178 @@ impl From<&str> for Header {
     }
 }
 
+impl From<String> for Header {
+    fn from(value: String) -> Self {
+        Header(value)
+    }
+}
+
+/// One mismatch found by `QvdDocument::schema_diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaDifference {
+    ColumnCount { left: usize, right: usize },
+    Name { position: usize, left: String, right: String },
+    Type { position: usize, left: ColumnType, right: ColumnType },
+}
+
+/// Result of `QvdDocument::diff`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentDiff {
+    pub schema: Vec<SchemaDifference>,
+    /// Indexes of the rows whose values differ, empty if the schemas differ.
+    pub rows: Vec<usize>,
+}
+
+impl DocumentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.schema.is_empty() && self.rows.is_empty()
+    }
+}
+
+/// Summary of one column, see `QvdDocument::describe`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    pub name: String,
+    pub dtype: ColumnType,
+    /// Rows holding a value.
+    pub count: usize,
+    pub null_count: usize,
+    pub distinct_count: usize,
+    /// Only set for `Int` and `Float` columns with at least one value.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+}
+
+/// Type of a column's values, see `Column::dtype` and `QvdDocument::cast_column`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColumnType {
+    Int,
+    Float,
+    Text,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum CellValue {
     Text(String),
     Int(i32),
     Float(f64),
+    /// A number stored along with its own display text, e.g. a date and its
+    /// formatted string. Displays as the text and compares as the number.
+    Dual(f64, String),
+    Null,
+}
+
+/// Hashable identity of a value, floats are compared by their bit pattern.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub(crate) enum SymbolKey<'a> {
+    Text(&'a str),
+    Int(i32),
+    Float(u64),
+    Dual(u64, &'a str),
     Null,
 }
 
+impl CellValue {
+    pub(crate) fn symbol_key(&self) -> SymbolKey<'_> {
+        match self {
+            CellValue::Text(s) => SymbolKey::Text(s),
+            CellValue::Int(i) => SymbolKey::Int(*i),
+            CellValue::Float(f) => SymbolKey::Float(f.to_bits()),
+            CellValue::Dual(f, s) => SymbolKey::Dual(f.to_bits(), s),
+            CellValue::Null => SymbolKey::Null,
+        }
+    }
+
+    /// Short label of the variant for diagnostics: `"text"`, `"int"`,
+    /// `"float"`, `"dual"` or `"null"`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CellValue::Text(_) => "text",
+            CellValue::Int(_) => "int",
+            CellValue::Float(_) => "float",
+            CellValue::Dual(..) => "dual",
+            CellValue::Null => "null",
+        }
+    }
+
+    // Equality treating NaN as equal to itself
+    pub(crate) fn same_value(&self, other: &CellValue) -> bool {
+        match (self, other) {
+            (CellValue::Float(a), CellValue::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+            _ => self == other,
+        }
+    }
+
+    // Like `same_value`, but `Int` and `Float` compare by value
+    pub(crate) fn same_number(&self, other: &CellValue) -> bool {
+        match (self.as_f64(), other.as_f64()) {
+            (Some(a), Some(b)) => a == b || (a.is_nan() && b.is_nan()),
+            _ => self.same_value(other),
+        }
+    }
+
+    fn cast(&self, to: ColumnType) -> Option<CellValue> {
+        let number = match self {
+            CellValue::Null => return Some(CellValue::Null),
+            CellValue::Text(s) if to != ColumnType::Text => parse_number(s).map(CellValue::from)?,
+            CellValue::Dual(f, _) if to != ColumnType::Text => CellValue::Float(*f),
+            other => other.clone(),
+        };
+        match (to, number) {
+            (ColumnType::Text, value) => Some(CellValue::Text(value.to_string())),
+            (ColumnType::Float, value) => value.as_f64().map(CellValue::Float),
+            (ColumnType::Int, CellValue::Float(f)) => {
+                let whole = f.fract() == 0. && f >= i32::MIN as f64 && f <= i32::MAX as f64;
+                whole.then_some(CellValue::Int(f as i32))
+            }
+            (ColumnType::Int, value) => Some(value),
+        }
+    }
+
+    /// Numeric value of `Int`, `Float` and `Dual` cells.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            CellValue::Int(i) => Some(*i as f64),
+            CellValue::Float(f) | CellValue::Dual(f, _) => Some(*f),
+            CellValue::Text(_) | CellValue::Null => None,
+        }
+    }
+
+    /// Like `as_f64`, but also parses numeric text such as `"865.2"`. Text
+    /// such as `"NaN"` or `"inf"` isn't numeric, as when reading string
+    /// symbols.
+    pub fn to_f64_lossy(&self) -> Option<f64> {
+        match self {
+            CellValue::Text(s) => s.trim().parse().ok().filter(|f: &f64| f.is_finite()),
+            other => other.as_f64(),
+        }
+    }
+
+    /// Total ordering across all variants: `Null` sorts first, then numbers
+    /// (including numeric text) by `f64::total_cmp`, then the remaining text
+    /// lexicographically.
+    pub fn total_cmp(&self, other: &CellValue) -> Ordering {
+        match (self, other) {
+            (CellValue::Null, CellValue::Null) => Ordering::Equal,
+            (CellValue::Null, _) => Ordering::Less,
+            (_, CellValue::Null) => Ordering::Greater,
+            _ => match (self.to_f64_lossy(), other.to_f64_lossy()) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => self.to_string().cmp(&other.to_string()),
+            },
+        }
+    }
+}
+
 impl Display for CellValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
-            CellValue::Text(s) => s,
+            CellValue::Text(s) | CellValue::Dual(_, s) => s,
             CellValue::Int(i) => &i.to_string(),
-            CellValue::Float(f) => &f.to_string(),
+            CellValue::Float(f) => &FloatFormat::Shortest.format(*f),
             CellValue::Null => &String::new(),
         };
         write!(f, "{s}")
@@ -245,6 +1544,170 @@ impl From<f64> for CellValue {
     }
 }
 
+impl From<String> for CellValue {
+    fn from(value: String) -> Self {
+        CellValue::Text(value)
+    }
+}
+
+/// QlikView has no boolean type, `true` is stored as 1 and `false` as 0.
+impl From<bool> for CellValue {
+    fn from(value: bool) -> Self {
+        CellValue::Int(value as i32)
+    }
+}
+
+fn conversion_error(value: &CellValue, to: &str) -> QvdError {
+    QvdError::new(QvdErrorKind::InvalidValue, format!("cannot convert {} '{value}' to {to}", value.type_name()))
+}
+
+/// Only `Int` values convert.
+impl TryFrom<&CellValue> for i32 {
+    type Error = QvdError;
+
+    fn try_from(value: &CellValue) -> Result<Self, Self::Error> {
+        match value {
+            CellValue::Int(i) => Ok(*i),
+            other => Err(conversion_error(other, "i32")),
+        }
+    }
+}
+
+/// `Int`, `Float` and `Dual` values convert, see `CellValue::as_f64`.
+impl TryFrom<&CellValue> for f64 {
+    type Error = QvdError;
+
+    fn try_from(value: &CellValue) -> Result<Self, Self::Error> {
+        value.as_f64().ok_or_else(|| conversion_error(value, "f64"))
+    }
+}
+
+/// Every value except `Null` converts, rendered by `Display`.
+impl TryFrom<&CellValue> for String {
+    type Error = QvdError;
+
+    fn try_from(value: &CellValue) -> Result<Self, Self::Error> {
+        match value {
+            CellValue::Null => Err(conversion_error(value, "String")),
+            other => Ok(other.to_string()),
+        }
+    }
+}
+
+/// Always succeeds, for columns that may hold nulls.
+impl TryFrom<&CellValue> for CellValue {
+    type Error = QvdError;
+
+    fn try_from(value: &CellValue) -> Result<Self, Self::Error> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: Into<CellValue>> From<Option<T>> for CellValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(CellValue::Null, Into::into)
+    }
+}
+
+/// A document decoded from an in-memory buffer whose text symbols borrow from
+/// that buffer instead of being copied. Use `to_owned` to detach it.
+#[derive(Debug)]
+pub struct QvdDocumentRef<'a> {
+    metadata: QvdMetadata,
+    columns: Vec<ColumnRef<'a>>,
+}
+
+impl<'a> QvdDocumentRef<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, QvdError> {
+        Self::from_bytes_with_options(bytes, &QvdReadOptions::default())
+    }
+
+    pub fn from_bytes_with_options(bytes: &'a [u8], options: &QvdReadOptions) -> Result<Self, QvdError> {
+        let (metadata, columns) = read_qvd_ref(bytes, options)?;
+        Ok(Self { metadata, columns })
+    }
+
+    pub fn metadata(&self) -> &QvdMetadata {
+        &self.metadata
+    }
+
+    pub fn columns(&self) -> &[ColumnRef<'a>] {
+        &self.columns
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnRef<'a>> {
+        self.columns.iter().find(|col| col.header.0 == name)
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.columns.first().map_or(0, |col| col.indexes.len())
+    }
+
+    pub fn to_owned(&self) -> QvdDocument {
+        let columns = self.columns.iter().map(ColumnRef::to_owned).collect();
+        QvdDocument { metadata: self.metadata.clone(), columns }
+    }
+}
+
+/// Column of a `QvdDocumentRef`.
+#[derive(Debug, PartialEq)]
+pub struct ColumnRef<'a> {
+    pub(crate) header: Header,
+    pub(crate) symbols: Vec<CellValueRef<'a>>,
+    pub(crate) indexes: Vec<isize>,
+}
+
+impl<'a> ColumnRef<'a> {
+    pub fn header(&self) -> Header {
+        self.header.clone()
+    }
+
+    pub fn resolve_index(&self, index: isize) -> CellValueRef<'a> {
+        usize::try_from(index).ok()
+            .and_then(|i| self.symbols.get(i).copied())
+            .unwrap_or(CellValueRef::Null)
+    }
+
+    pub fn as_values(&self) -> Vec<CellValueRef<'a>> {
+        self.indexes.iter().map(|&idx| self.resolve_index(idx)).collect()
+    }
+
+    pub fn to_owned(&self) -> Column {
+        Column {
+            header: self.header.clone(),
+            symbols: self.symbols.iter().map(CellValueRef::to_owned).collect(),
+            indexes: self.indexes.clone(),
+        }
+    }
+}
+
+/// Borrowed form of `CellValue`, as stored in a `ColumnRef`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CellValueRef<'a> {
+    Text(&'a str),
+    Int(i32),
+    Float(f64),
+    Dual(f64, &'a str),
+    Null,
+}
+
+impl CellValueRef<'_> {
+    pub fn to_owned(&self) -> CellValue {
+        CellValue::from(*self)
+    }
+}
+
+impl From<CellValueRef<'_>> for CellValue {
+    fn from(value: CellValueRef<'_>) -> Self {
+        match value {
+            CellValueRef::Text(s) => CellValue::Text(s.into()),
+            CellValueRef::Int(i) => CellValue::Int(i),
+            CellValueRef::Float(f) => CellValue::Float(f),
+            CellValueRef::Dual(f, s) => CellValue::Dual(f, s.into()),
+            CellValueRef::Null => CellValue::Null,
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -290,6 +1753,61 @@ mod tests {
         assert_eq!(row_indexes, vec![6,7]);
     }
 
+    #[test]
+    fn test_filter_between() {
+        let column = Column {
+            header: Header("Integer".into()),
+            symbols: {
+                (1..=10).map(|i| {  CellValue::Int(i) }).collect()
+            },
+            indexes: vec![9,8,7,6,5,4,3,2,1,0,-2,2],
+        };
+        assert_eq!(column.filter_between(3.into(), 7.into()), vec![3,4,5,6,7,11]);
+        assert_eq!(column.filter_between(8.5.into(), "100".into()), vec![0,1]);
+        assert!(column.filter_between(7.into(), 3.into()).is_empty());
+    }
+
+    #[test]
+    fn test_unique_referenced_values() {
+        let column = Column {
+            header: Header("Quarter".into()),
+            symbols: vec!["Q1".into(), "Q2".into(), "Q3".into()],
+            indexes: vec![2, 0, -2, 2],
+        };
+        assert_eq!(column.unique_referenced_values(), vec![&CellValue::from("Q1"), &CellValue::from("Q3")]);
+    }
+
+    #[test]
+    fn test_row_indexes_numeric() {
+        let column = Column {
+            header: Header("Number".into()),
+            symbols: vec![CellValue::Float(1.), CellValue::Int(2), CellValue::Text("1".into())],
+            indexes: vec![0, 1, 2, 0],
+        };
+        assert!(column.find_row_indexes(1).is_empty());
+        assert_eq!(column.find_row_indexes_numeric(1), vec![0, 3]);
+        assert_eq!(column.find_row_indexes_numeric(2.), vec![1]);
+    }
+
+    #[test]
+    fn test_type_name() {
+        assert_eq!(CellValue::from("Q1").type_name(), "text");
+        assert_eq!(CellValue::Int(1).type_name(), "int");
+        assert_eq!(CellValue::Float(1.5).type_name(), "float");
+        assert_eq!(CellValue::Null.type_name(), "null");
+    }
+
+    #[test]
+    fn test_row_indexes_for_nan() {
+        let column = Column {
+            header: Header("Float".into()),
+            symbols: vec![CellValue::Float(1.), CellValue::Float(f64::NAN)],
+            indexes: vec![0,1,-2,1],
+        };
+        assert_eq!(column.find_row_indexes(f64::NAN), vec![1,3]);
+        assert_eq!(column.find_row_indexes(1.), vec![0]);
+    }
+
     #[test]
     fn test_value_from_row_index() {
         let column = Column {
@@ -304,16 +1822,871 @@ mod tests {
     }
 
     #[test]
-    fn test_qvd_document_rows() {
+    fn test_split_dual_column() {
+        use crate::QvdWriter;
+
+        let mut writer = QvdWriter::new("duals", &["id", "date"]);
+        for (id, date) in [(1, CellValue::Dual(43831., "2020-01-01".into())), (2, CellValue::Null), (3, CellValue::Dual(43832., "2020-01-02".into()))] {
+            writer.push_row(&[id.into(), date]).unwrap();
+        }
+        let mut qvd = Vec::new();
+        writer.finish(&mut qvd).unwrap();
+        let mut doc = QvdDocument::from_bytes(&qvd).unwrap();
+        assert!(doc.column("date").unwrap().is_dual());
+        assert!(!doc.column("id").unwrap().is_dual());
+
+        doc.split_dual_column("date", "date_num", "date_text").unwrap();
+        assert_eq!(doc.column_names(), vec!["id", "date_num", "date_text"]);
+        let numbers = doc.column("date_num").unwrap().as_values();
+        assert_eq!(numbers, vec![&CellValue::Float(43831.), &CellValue::Null, &CellValue::Float(43832.)]);
+        let texts = doc.column("date_text").unwrap().as_values();
+        assert_eq!(texts, vec![&CellValue::from("2020-01-01"), &CellValue::Null, &CellValue::from("2020-01-02")]);
+
+        let err = doc.split_dual_column("date_num", "id", "x").unwrap_err();
+        assert!(matches!(err.kind(), QvdErrorKind::DuplicateColumn { .. }));
+    }
+
+    #[test]
+    fn test_from_parts() {
+        use crate::raw::{Fields, QvdFieldHeader, QvdTableHeader};
+
+        let header = QvdTableHeader {
+            table_name: "parts".into(),
+            creator_doc: String::new(),
+            fields: Fields {
+                headers: vec![QvdFieldHeader { field_name: "x".into(), offset: 0, length: 10, bit_offset: 0, bit_width: 1, bias: 0, tags: Vec::new() }],
+            },
+            no_of_records: 3,
+            record_byte_size: 1,
+            offset: 10,
+            length: 3,
+        };
+        let data = [1, 7, 0, 0, 0, 4, b'a', b'b', b'c', 0, 0, 1, 1];
+        let doc = QvdDocument::from_parts(header, &data).unwrap();
+        assert_eq!(doc.metadata().table_name(), "parts");
+        assert_eq!(doc.column("x").unwrap().as_values(), vec![&CellValue::Int(7), &CellValue::from("abc"), &CellValue::from("abc")]);
+    }
+
+    #[test]
+    fn test_describe() {
         let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
-        let mut rows = doc.rows();
-        let expected = [1.into(), "Q1".into(), 1.1.into(), 1.2.into(), CellValue::Null];
-        let expected: Vec<_> = expected.iter().collect();
-        assert_eq!(rows.next(), Some(expected));
+        let stats = doc.describe();
+        assert_eq!(stats.len(), 5);
+        let types: Vec<_> = stats.iter().map(|col| col.dtype).collect();
+        assert_eq!(types, vec![ColumnType::Int, ColumnType::Text, ColumnType::Float, ColumnType::Float, ColumnType::Text]);
 
-        let expected = [2.into(), "Q1".into(), 2.2.into(), 10.0.into(), CellValue::Null];
-        let expected: Vec<_> = expected.iter().collect();
+        let all_int = &stats[0];
+        assert_eq!((all_int.count, all_int.null_count, all_int.distinct_count), (12, 0, 12));
+        assert_eq!((all_int.min, all_int.max, all_int.mean), (Some(1.), Some(12.), Some(6.5)));
+        assert_eq!((stats[1].distinct_count, stats[1].min), (4, None));
+
+        let some_null = &stats[3];
+        assert_eq!((some_null.count, some_null.null_count), (9, 3));
+        assert_eq!(some_null.mean, doc.column_mean("some_null").ok());
+
+        let all_null = &stats[4];
+        assert_eq!((all_null.count, all_null.null_count, all_null.distinct_count, all_null.mean), (0, 12, 0, None));
+    }
+
+    #[test]
+    fn test_read_at() {
+        // tests/embedded.qvd is tests/test_file.qvd behind a 19 byte prefix
+        let doc = QvdDocument::read_at("tests/embedded.qvd", 19).unwrap();
+        assert_eq!(doc.columns(), QvdDocument::read("tests/test_file.qvd").unwrap().columns());
+        assert!(QvdDocument::read_at("tests/embedded.qvd", 0).is_err());
+    }
+
+    #[test]
+    fn test_to_string_vec() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        for column in doc.columns() {
+            let generic: Vec<_> = column.as_values().iter().map(|value| value.to_string()).collect();
+            assert_eq!(column.to_string_vec(), generic);
+        }
+        assert_eq!(doc.column("all_string").unwrap().to_string_vec()[..2], ["Q1", "Q1"]);
+    }
+
+    #[test]
+    fn test_find_row_indexes_limited() {
+        let column = Column::from_values("col", [1, 2, 1, 1, 2, 1].map(CellValue::Int).to_vec());
+        assert_eq!(column.find_row_indexes_limited(1, 2), vec![0, 2]);
+        assert_eq!(column.find_row_indexes_limited(2, 5), vec![1, 4]);
+        assert_eq!(column.find_row_indexes_limited(1, 0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_rows_where() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let rows: Vec<_> = doc.rows_where("all_string", "Q2", &["all_int", "some_null"]).unwrap().collect();
+        assert_eq!(rows, vec![
+            vec![&CellValue::Int(4), &CellValue::Null],
+            vec![&CellValue::Int(5), &CellValue::Null],
+            vec![&CellValue::Int(6), &CellValue::Null],
+        ]);
+        let err = doc.rows_where("all_string", "Q2", &["missing"]).err().unwrap();
+        assert!(matches!(err.kind(), QvdErrorKind::ColumnNotFound { .. }));
+    }
+
+    #[test]
+    fn test_typed_rows() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let rows: Vec<(i32, String, f64)> = doc.rows_where("all_string", "Q1", &["all_int", "all_string", "all_float"])
+            .unwrap()
+            .typed()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows[0], (1, "Q1".to_string(), 1.1));
+        assert_eq!(rows.len(), 3);
+
+        let nulls = doc.rows().typed::<(i32, String, f64, f64, CellValue)>().collect::<Result<Vec<_>, _>>();
+        assert_eq!(nulls.unwrap_err().kind(), &QvdErrorKind::InvalidValue);
+        let arity = doc.rows().typed::<(i32, String)>().next().unwrap();
+        assert_eq!(arity.unwrap_err().kind(), &QvdErrorKind::SchemaMismatch);
+    }
+
+    #[test]
+    fn test_merge_columns() {
+        let ints = QvdDocument::read("tests/ints.qvd").unwrap();
+        let floats = QvdDocument::read("tests/floats.qvd").unwrap();
+        let merged = ints.merge_columns(&floats).unwrap();
+        assert_eq!(merged.column_names(), vec!["RowNo()", "Id", "Number"]);
+        assert_eq!(merged.rows().nth(2).unwrap(), vec![&CellValue::Int(3), &CellValue::Int(3), &CellValue::Float(3.1)]);
+
+        let err = floats.merge_columns(&floats).unwrap_err();
+        assert!(matches!(err.kind(), QvdErrorKind::DuplicateColumn { .. }));
+        let err = ints.merge_columns(&QvdDocument::read("tests/test_file.qvd").unwrap()).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::SchemaMismatch);
+    }
+
+    #[test]
+    fn test_sorted_symbols() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let quarters = doc.column("all_string").unwrap().sorted_symbols();
+        assert_eq!(quarters, ["Q1", "Q2", "Q3", "Q4"].map(CellValue::from).iter().collect::<Vec<_>>());
+
+        let column = Column::from_values("col", vec![CellValue::Float(2.5), CellValue::Null, CellValue::Int(-1), CellValue::Int(2), CellValue::Int(-1)]);
+        assert_eq!(column.sorted_symbols(), vec![&CellValue::Int(-1), &CellValue::Int(2), &CellValue::Float(2.5)]);
+    }
+
+    #[test]
+    fn test_symbols_enumerated() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let column = doc.column("all_string").unwrap();
+        let pairs: Vec<_> = column.symbols_enumerated().collect();
+        assert_eq!(pairs, ["Q1", "Q2", "Q3", "Q4"].map(CellValue::from).iter().enumerate().collect::<Vec<_>>());
+        for (idx, symbol) in column.symbols_enumerated() {
+            assert_eq!(column.resolve_index(idx as isize), symbol);
+        }
+    }
+
+    #[test]
+    fn test_replace_nulls() {
+        let mut doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let column = &mut doc.columns_mut()[3];
+        let symbols = column.symbols.len();
+        column.replace_nulls(CellValue::Int(0));
+        assert_eq!(column.null_count(), 0);
+        assert_eq!(column.symbols.len(), symbols + 1);
+        assert_eq!(column.as_values()[2..6], [&CellValue::Int(64), &CellValue::Int(0), &CellValue::Int(0), &CellValue::Int(0)]);
+
+        column.replace_nulls(CellValue::Int(64));
+        assert_eq!(column.symbols.len(), symbols + 1);
+    }
+
+    #[test]
+    fn test_schema_diff() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert!(doc.schema_compatible(&doc));
+
+        let mut renamed = QvdDocument::read("tests/test_file.qvd").unwrap();
+        renamed.rename_column("all_int", "id").unwrap();
+        assert!(!doc.schema_compatible(&renamed));
+        assert_eq!(doc.schema_diff(&renamed), vec![
+            SchemaDifference::Name { position: 0, left: "all_int".into(), right: "id".into() },
+        ]);
+
+        let ints = QvdDocument::read("tests/ints.qvd").unwrap();
+        assert_eq!(ints.schema_diff(&doc)[..2], [
+            SchemaDifference::ColumnCount { left: 1, right: 5 },
+            SchemaDifference::Name { position: 0, left: "RowNo()".into(), right: "all_int".into() },
+        ]);
+    }
+
+    #[test]
+    fn test_read_expecting() {
+        let doc = QvdDocument::read_expecting("tests/floats.qvd", &[("Id", ColumnType::Int), ("Number", ColumnType::Float)]).unwrap();
+        assert_eq!(doc.row_count(), 3);
+
+        let err = QvdDocument::read_expecting("tests/floats.qvd", &[("Id", ColumnType::Text), ("Value", ColumnType::Float)]).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::SchemaMismatch);
+        let message = err.to_string();
+        assert!(message.contains("Type { position: 0, left: Int, right: Text }"), "{message}");
+        assert!(message.contains("Name { position: 1, left: \"Number\", right: \"Value\" }"), "{message}");
+    }
+
+    #[test]
+    fn test_read_schema_then_data() {
+        let mut reader = BufReader::new(std::fs::File::open("tests/test_file.qvd").unwrap());
+        let metadata = QvdDocument::read_schema_from_reader(&mut reader).unwrap();
+        assert_eq!(metadata.table_name(), "TempData2");
+        assert_eq!(metadata.no_of_records(), 12);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len(), metadata.offset() + metadata.length());
+
+        let mut reader = BufReader::new(std::fs::File::open("tests/test_file.qvd").unwrap());
+        let header = QvdDocument::read_header_from_reader(&mut reader).unwrap();
+        assert_eq!(header.fields.headers.len(), 5);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        let doc = QvdDocument::from_parts(header, &data).unwrap();
+        assert_eq!(doc.columns(), QvdDocument::read("tests/test_file.qvd").unwrap().columns());
+    }
+
+    #[test]
+    fn test_document_builder() {
+        let doc = QvdDocumentBuilder::new()
+            .add_column("id", vec![1.into(), 2.into()])
+            .add_column("name", vec!["a".into(), CellValue::Null])
+            .add_column("score", vec![1.5.into(), 1.5.into()])
+            .build()
+            .unwrap();
+        assert_eq!(doc.column_names(), vec!["id", "name", "score"]);
+        assert_eq!(doc.rows().collect::<Vec<_>>(), vec![
+            vec![&CellValue::Int(1), &CellValue::from("a"), &CellValue::Float(1.5)],
+            vec![&CellValue::Int(2), &CellValue::Null, &CellValue::Float(1.5)],
+        ]);
+
+        let err = QvdDocumentBuilder::new().add_column("a", vec![1.into()]).add_column("b", vec![]).build().unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::SchemaMismatch);
+        let err = QvdDocumentBuilder::new().add_column("a", vec![]).add_column("a", vec![]).build().unwrap_err();
+        assert!(matches!(err.kind(), QvdErrorKind::DuplicateColumn { .. }));
+    }
+
+    #[test]
+    fn test_diff() {
+        let golden = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert!(golden.diff(&golden).is_empty());
+
+        let mut mutated = QvdDocumentBuilder::new();
+        for col in golden.columns() {
+            let mut values = col.clone().into_values();
+            if col.header.0 == "all_float" {
+                values[7] = CellValue::Float(0.);
+            }
+            mutated = mutated.add_column(col.header.0.clone(), values);
+        }
+        let mutated = mutated.build().unwrap();
+        assert_eq!(golden.diff(&mutated), DocumentDiff { schema: vec![], rows: vec![7] });
+
+        let mut renamed = QvdDocument::read("tests/test_file.qvd").unwrap();
+        renamed.rename_column("all_int", "id").unwrap();
+        let diff = golden.diff(&renamed);
+        assert_eq!((diff.schema.len(), diff.rows.len()), (1, 0));
+    }
+
+    #[test]
+    fn test_map_rows() {
+        #[derive(Debug, PartialEq)]
+        struct Quarter<'a> {
+            id: i32,
+            name: &'a CellValue,
+        }
+
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let rows = doc.rows();
+        assert_eq!(rows.len(), 12);
+        let quarters: Vec<_> = rows
+            .map(|row| Quarter { id: i32::try_from(row[0]).unwrap(), name: row[1] })
+            .filter(|quarter| quarter.id % 4 == 0)
+            .collect();
+        assert_eq!(quarters[0], Quarter { id: 4, name: &CellValue::from("Q2") });
+        assert_eq!(quarters.len(), 3);
+    }
+
+    #[test]
+    fn test_approx_size_bytes() {
+        let tiny = Column::from_values("flag", vec![CellValue::Int(1); 10]);
+        let wide = Column::from_values("name", (0..10).map(|i| CellValue::from(format!("customer {i}"))).collect());
+        assert!(wide.approx_size_bytes() > tiny.approx_size_bytes());
+
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(doc.approx_size_bytes(), doc.columns().iter().map(Column::approx_size_bytes).sum::<usize>());
+    }
+
+    #[test]
+    fn test_column_get() {
+        let column = Column::from_values("col", vec![CellValue::Int(1), CellValue::Null, CellValue::Int(1)]);
+        assert_eq!(column.get(0), Some(CellValue::Int(1)));
+        assert_eq!(column.get(1), Some(CellValue::Null));
+        assert_eq!(column.get(3), None);
+    }
+
+    #[test]
+    fn test_resolve_index_past_symbols() {
+        let column = Column {
+            header: Header("Quarter".into()),
+            symbols: {
+                (1..=4).map(|i| {  CellValue::Text(format!("Q{}", i))}).collect()
+            },
+            indexes: vec![0,1,4,-2,3],
+        };
+        assert_eq!(column.resolve_index(4), &CellValue::Null);
+        assert_eq!(column.resolve_index(3), &CellValue::Text("Q4".into()));
+        let expected = ["Q1".into(), "Q2".into(), CellValue::Null, CellValue::Null, "Q4".into()];
+        assert_eq!(column.as_values(), expected.iter().collect::<Vec<_>>());
+        assert_eq!(column.indexes_to_values(&[2]), vec![&CellValue::Null]);
+        assert_eq!(column.into_values(), expected.to_vec());
+    }
+
+    #[test]
+    fn test_to_f64_lossy() {
+        assert_eq!(CellValue::Text("865.2".into()).to_f64_lossy(), Some(865.2));
+        assert_eq!(CellValue::Text("Q1".into()).to_f64_lossy(), None);
+        assert_eq!(CellValue::Int(3).to_f64_lossy(), Some(3.));
+        assert_eq!(CellValue::Float(1.5).to_f64_lossy(), Some(1.5));
+        assert_eq!(CellValue::Null.to_f64_lossy(), None);
+        assert_eq!(CellValue::Text("865.2".into()).as_f64(), None);
+    }
+
+    #[test]
+    fn test_non_finite_text_is_not_numeric() {
+        for text in ["NaN", "inf", " -infinity ", "Infinity"] {
+            assert_eq!(CellValue::Text(text.into()).to_f64_lossy(), None, "{text}");
+        }
+        // Sorted with the other text, after every number
+        assert_eq!(CellValue::from("-inf").total_cmp(&CellValue::Int(1)), Ordering::Greater);
+
+        let doc = QvdDocument::from_columns(vec![
+            Column::from_values("x", vec!["1.5".into(), "NaN".into(), "inf".into(), CellValue::Int(2)]),
+        ]);
+        assert_eq!(doc.column_sum("x").unwrap(), 3.5);
+        assert_eq!(doc["x"].histogram(1).unwrap(), vec![(1.5, 2., 2)]);
+    }
+
+    #[test]
+    fn test_total_cmp() {
+        assert_eq!(CellValue::Int(2).total_cmp(&CellValue::Float(10.)), Ordering::Less);
+        assert_eq!(CellValue::Text("10.5".into()).total_cmp(&CellValue::Int(3)), Ordering::Greater);
+        assert_eq!(CellValue::Int(1).total_cmp(&CellValue::Float(1.)), Ordering::Equal);
+        assert_eq!(CellValue::Null.total_cmp(&CellValue::Int(-5)), Ordering::Less);
+        assert_eq!(CellValue::Int(1000).total_cmp(&"Q1".into()), Ordering::Less);
+        assert_eq!(CellValue::from("Q2").total_cmp(&"Q1".into()), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_column_min_max() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(doc.column_min("all_int"), Some(CellValue::Int(1)));
+        assert_eq!(doc.column_max("all_int"), Some(CellValue::Int(12)));
+        assert_eq!(doc.column_max("some_null"), Some(CellValue::Int(1000)));
+        assert_eq!(doc.column_min("all Null"), None);
+        assert_eq!(doc.column_max("all Null"), None);
+        assert_eq!(doc.column_max("missing"), None);
+    }
+
+    #[test]
+    fn test_column_sum_mean() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let sum = doc.column_sum("all_float").unwrap();
+        assert!((sum - 82.83).abs() < 1e-9);
+        let mean = doc.column_mean("all_float").unwrap();
+        assert!((mean - 82.83 / 12.).abs() < 1e-9);
+        // nulls are skipped, not counted as zero
+        let mean = doc.column_mean("some_null").unwrap();
+        let expected = (1.2 + 10. + 64. + 1. + 213.95625 + 2. + 3. + 5. + 1000.) / 9.;
+        assert!((mean - expected).abs() < 1e-9);
+        assert!(doc.column_sum("all Null").is_err());
+        assert!(doc.column_sum("all_string").is_err());
+        assert!(doc.column_mean("missing").is_err());
+    }
+
+    #[test]
+    fn test_retain_and_drop_columns() {
+        let mut doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        doc.drop_columns(&["all_float", "missing"]);
+        assert_eq!(doc.column_names(), vec!["all_int", "all_string", "some_null", "all Null"]);
+        assert_eq!(doc.row_count(), 12);
+
+        doc.retain_columns(&["some_null", "all_int"]);
+        assert_eq!(doc.column_names(), vec!["all_int", "some_null"]);
+        assert_eq!(doc["some_null"].find_null_row_indexes(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_rename_column() {
+        let mut doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        doc.rename_column("all Null", "all_null").unwrap();
+        assert!(doc.column("all Null").is_none());
+        assert_eq!(doc.column("all_null").unwrap().header(), Header::from("all_null"));
+        assert_eq!(doc.columns()[4].header(), Header::from("all_null"));
+
+        let err = doc.rename_column("all_int", "all_string").unwrap_err();
+        assert!(matches!(err.kind(), QvdErrorKind::DuplicateColumn { .. }));
+        let err = doc.rename_column("all Null", "x").unwrap_err();
+        assert!(matches!(err.kind(), QvdErrorKind::ColumnNotFound { .. }));
+    }
+
+    #[test]
+    fn test_column_from_values() {
+        let values = vec!["Q1".into(), CellValue::Null, 2.into(), "Q1".into(), 2.into()];
+        let column = Column::from_values("mixed", values.clone());
+        assert_eq!(column.symbols, vec!["Q1".into(), 2.into()]);
+        assert_eq!(column.indexes, vec![0, NULL_INDEX, 1, 0, 1]);
+        assert_eq!(column.into_values(), values);
+    }
+
+    #[test]
+    fn test_with_column() {
+        let mut doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        doc.with_column("int+string", |row| format!("{}-{}", row[0], row[1]).as_str().into()).unwrap();
+        let column = doc.column("int+string").unwrap();
+        assert_eq!(column.indexes.len(), 12);
+        assert_eq!(column.resolve_row(0), &CellValue::from("1-Q1"));
+        assert_eq!(column.resolve_row(11), &CellValue::from("12-Q4"));
+        assert!(doc.with_column("all_int", |_| CellValue::Null).is_err());
+    }
+
+    #[test]
+    fn test_inner_join() {
+        let left = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut right = QvdDocument::read("tests/floats.qvd").unwrap();
+        right.rename_column("Id", "all_int").unwrap();
+        let joined = left.inner_join(&right, "all_int").unwrap();
+        let names: Vec<_> = joined.columns().iter().map(|col| col.header().0).collect();
+        assert_eq!(names, vec!["all_int", "all_string", "all_float", "some_null", "all Null", "Number"]);
+        let rows: Vec<_> = joined.rows_owned().collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2], vec![3.into(), "Q1".into(), 3.3.into(), 64.into(), CellValue::Null, 3.1.into()]);
+    }
+
+    #[test]
+    fn test_inner_join_suffixes_collisions() {
+        let left = QvdDocument::read("tests/floats.qvd").unwrap();
+        let right = QvdDocument::read("tests/floats.qvd").unwrap();
+        let joined = left.inner_join(&right, "Id").unwrap();
+        let names: Vec<_> = joined.columns().iter().map(|col| col.header().0).collect();
+        assert_eq!(names, vec!["Id", "Number", "Number_right"]);
+        assert_eq!(joined.row_count(), 3);
+        assert!(left.inner_join(&right, "missing").is_err());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let metadata = doc.metadata();
+        assert_eq!(metadata.table_name(), "TempData2");
+        assert_eq!(metadata.no_of_records(), 12);
+        assert!(metadata.record_byte_size() > 0);
+        assert_eq!(metadata.record_byte_size(), 2);
+        assert_eq!(metadata.offset(), 237);
+        assert_eq!(metadata.length(), 24);
+    }
+
+    #[test]
+    fn test_data_section_len() {
+        for path in ["tests/test_file.qvd", "tests/ints.qvd"] {
+            let metadata = QvdDocument::read(path).unwrap().metadata().clone();
+            assert_eq!(metadata.data_section_len(), metadata.no_of_records() * metadata.record_byte_size());
+        }
+    }
+
+    #[test]
+    fn test_equals_ignoring_order() {
+        let doc = |symbols: Vec<CellValue>, indexes: Vec<isize>| QvdDocument::from_columns(vec![
+            Column { header: Header("Quarter".into()), symbols, indexes },
+        ]);
+        let a = doc(vec!["Q1".into(), "Q2".into()], vec![0, 1, 0, -2]);
+        let reordered = doc(vec!["Q2".into(), "Q1".into()], vec![1, 0, 1, -2]);
+        let shuffled_rows = doc(vec!["Q2".into(), "Q1".into()], vec![-2, 1, 1, 0]);
+        let different = doc(vec!["Q2".into(), "Q1".into()], vec![1, 0, 0, -2]);
+        assert_ne!(a.columns(), reordered.columns());
+        assert!(a.equals_ignoring_order(&reordered));
+        assert!(a.equals_ignoring_order(&shuffled_rows));
+        assert!(!a.equals_ignoring_order(&different));
+
+        let file = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert!(file.equals_ignoring_order(&QvdDocument::read("tests/test_file.qvd").unwrap()));
+        assert!(!file.equals_ignoring_order(&a));
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let first = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut second = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(first.content_hash(), second.content_hash());
+        second.columns[1].symbols[0] = "Q0".into();
+        assert_ne!(first.content_hash(), second.content_hash());
+
+        let doc = |symbols: Vec<CellValue>, indexes: Vec<isize>| QvdDocument::from_columns(vec![
+            Column { header: Header("Quarter".into()), symbols, indexes },
+        ]);
+        let a = doc(vec!["Q1".into(), "Q2".into()], vec![0, 1, 0]);
+        let reordered = doc(vec!["Q2".into(), "Q1".into()], vec![0, 1, 1]);
+        assert_eq!(a.content_hash(), reordered.content_hash());
+    }
+
+    #[test]
+    fn test_document_without_columns() {
+        let doc = QvdDocument::from_columns(Vec::new());
+        assert_eq!(doc.row_count(), 0);
+        assert!(doc.rows().next().is_none());
+        assert!(doc.rows_by_indexes(&[0, 1]).next().is_none());
+        assert_eq!(doc.par_rows().count(), 0);
+    }
+
+    #[test]
+    fn test_from_bool() {
+        assert_eq!(CellValue::from(true), CellValue::Int(1));
+        assert_eq!(CellValue::from(false), CellValue::Int(0));
+    }
+
+    #[test]
+    fn test_from_option() {
+        let values: Vec<CellValue> = vec![Some(1).into(), None::<i32>.into(), Some("Q1").into(), Some(String::from("Q2")).into()];
+        assert_eq!(values, vec![CellValue::Int(1), CellValue::Null, "Q1".into(), "Q2".into()]);
+        assert_eq!(CellValue::from(Some(1.5)), CellValue::Float(1.5));
+    }
+
+    #[test]
+    fn test_qvd_document_rows() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut rows = doc.rows();
+        let expected = [1.into(), "Q1".into(), 1.1.into(), 1.2.into(), CellValue::Null];
+        let expected: Vec<_> = expected.iter().collect();
         assert_eq!(rows.next(), Some(expected));
+
+        let expected = [2.into(), "Q1".into(), 2.2.into(), 10.0.into(), CellValue::Null];
+        let expected: Vec<_> = expected.iter().collect();
+        assert_eq!(rows.next(), Some(expected));
+    }
+
+    #[test]
+    fn test_row_iter_nth() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut rows = doc.rows();
+        let expected = [6.into(), "Q2".into(), 6.6.into(), CellValue::Null, CellValue::Null];
+        let expected: Vec<_> = expected.iter().collect();
+        assert_eq!(rows.nth(5), Some(expected));
+        assert_eq!(rows.next().unwrap()[0], &CellValue::Int(7));
+        assert_eq!(rows.nth(4), Some(vec![&CellValue::Int(12), &"Q4".into(), &12.12.into(), &1000.into(), &CellValue::Null]));
+        assert!(rows.next().is_none());
+        assert!(doc.rows().nth(100).is_none());
+    }
+
+    #[test]
+    fn test_par_rows_matches_serial() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let parallel: Vec<_> = doc.par_rows().collect();
+        let serial: Vec<_> = doc.rows_owned().collect();
+        assert_eq!(parallel.len(), doc.row_count());
+        assert_eq!(parallel, serial);
+    }
+
+    #[test]
+    fn test_read_glob() {
+        let doc = QvdDocument::read_glob("tests/partitioned/*.qvd").unwrap();
+        assert_eq!(doc.row_count(), 6);
+        let values = doc.columns()[0].as_values();
+        let expected: Vec<_> = (1..=6).map(CellValue::Int).collect();
+        assert_eq!(values, expected.iter().collect::<Vec<_>>());
+        assert!(QvdDocument::read_glob("tests/partitioned/*.csv").is_err());
+    }
+
+    #[test]
+    fn test_concat_merges_symbols() {
+        let first = QvdDocument::read("tests/ints.qvd").unwrap();
+        let second = QvdDocument::read("tests/ints.qvd").unwrap();
+        let doc = QvdDocument::concat(vec![first, second]).unwrap();
+        assert_eq!(doc.columns()[0].symbols.len(), 3);
+        assert_eq!(doc.columns()[0].indexes, vec![0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_concat_schema_mismatch() {
+        let ints = QvdDocument::read("tests/ints.qvd").unwrap();
+        let floats = QvdDocument::read("tests/floats.qvd").unwrap();
+        assert!(QvdDocument::concat(vec![ints, floats]).is_err());
+    }
+
+    #[test]
+    fn test_null_mask() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let column = doc.column("some_null").unwrap();
+        let expected: Vec<_> = column.indexes.iter().map(|&idx| idx != -2).collect();
+        assert_eq!(column.null_mask(), expected);
+        assert_eq!(column.null_mask()[3..6], [false, false, false]);
+        assert!(doc.column("all Null").unwrap().null_mask().iter().all(|valid| !valid));
+
+        // A symbol that decoded as null, e.g. from invalid UTF-8, and an out-of-range index
+        let column = Column {
+            header: Header("x".into()),
+            symbols: vec![CellValue::Text("a".into()), CellValue::Null],
+            indexes: vec![0, 1, -2, 5],
+        };
+        assert_eq!(column.null_mask(), vec![true, false, false, false]);
+    }
+
+    #[test]
+    fn test_borrowed_document() {
+        let bytes = std::fs::read("tests/test_file.qvd").unwrap();
+        let borrowed = QvdDocumentRef::from_bytes(&bytes).unwrap();
+        let owned = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(borrowed.row_count(), owned.row_count());
+        assert_eq!(borrowed.metadata().table_name(), "TempData2");
+
+        let quarters = borrowed.column("all_string").unwrap().as_values();
+        assert_eq!(quarters[0], CellValueRef::Text("Q1"));
+        // the text points into the input buffer rather than a copy
+        let CellValueRef::Text(text) = quarters[0] else { unreachable!() };
+        assert!(bytes.as_ptr_range().contains(&text.as_ptr()));
+
+        let detached = borrowed.to_owned();
+        assert_eq!(detached.columns(), owned.columns());
+        assert_eq!(QvdDocument::from_bytes(&bytes).unwrap().columns(), owned.columns());
+    }
+
+    #[test]
+    fn test_try_rows_by_indexes() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(doc.try_rows_by_indexes(&[0, 11]).unwrap().count(), 2);
+        let err = doc.try_rows_by_indexes(&[0, 12]).err().unwrap();
+        assert!(matches!(err.kind(), QvdErrorKind::InvalidValue));
+        assert_eq!(doc.rows_by_indexes(&[12]).next().unwrap(), vec![&CellValue::Null; 5]);
+    }
+
+    #[test]
+    fn test_iterate_document_by_reference() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut rows = 0;
+        for row in &doc {
+            assert_eq!(row.len(), 5);
+            rows += 1;
+        }
+        assert_eq!(rows, 12);
+    }
+
+    #[test]
+    fn test_cast_column() {
+        let mut doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        doc.cast_column("some_null", ColumnType::Float).unwrap();
+        let values = doc.column("some_null").unwrap().as_values();
+        assert!(values.iter().all(|v| matches!(v, CellValue::Float(_) | CellValue::Null)));
+        assert_eq!(values[2], &CellValue::Float(64.));
+        assert_eq!(values[3], &CellValue::Null);
+
+        doc.cast_column("all_int", ColumnType::Text).unwrap();
+        assert_eq!(doc.column("all_int").unwrap().as_values()[0], &CellValue::from("1"));
+        doc.cast_column("all_int", ColumnType::Int).unwrap();
+        assert_eq!(doc.column("all_int").unwrap().as_values()[0], &CellValue::Int(1));
+
+        let err = doc.cast_column("all_string", ColumnType::Int).unwrap_err();
+        assert!(matches!(err.kind(), QvdErrorKind::InvalidValue));
+        assert!(err.message().contains("'Q1'"));
+        assert_eq!(doc.column("all_string").unwrap().as_values()[0], &CellValue::from("Q1"));
+        assert!(doc.cast_column("some_null", ColumnType::Int).is_err());
+    }
+
+    #[test]
+    fn test_rechunk() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let rows = doc.find_row_indexes("all_string", "Q3");
+        let mut filtered = QvdDocument::from_columns(doc.columns().iter().map(|col| col.take(&rows)).collect());
+        let before: Vec<Vec<CellValue>> = filtered.rows_owned().collect();
+        filtered.rechunk();
+        assert_eq!(filtered.rows_owned().collect::<Vec<_>>(), before);
+
+        let quarters = filtered.column("all_string").unwrap();
+        assert_eq!(quarters.symbols, vec![CellValue::from("Q3")]);
+        for column in filtered.columns() {
+            assert_eq!(column.unique_referenced_values().len(), column.symbols.len());
+        }
+        let all_null = filtered.column("all Null").unwrap();
+        assert!(all_null.symbols.is_empty());
+        assert!(all_null.indexes.iter().all(|&idx| idx == NULL_INDEX));
+    }
+
+    #[test]
+    fn test_rechunk_merges_duplicate_symbols() {
+        let mut column = Column {
+            header: Header("Number".into()),
+            symbols: vec![CellValue::Int(1), CellValue::Int(2), CellValue::Int(1)],
+            indexes: vec![2, 0, -2, 5],
+        };
+        column.rechunk();
+        assert_eq!(column.symbols, vec![CellValue::Int(1)]);
+        assert_eq!(column.indexes, vec![0, 0, NULL_INDEX, NULL_INDEX]);
+    }
+
+    #[test]
+    fn test_columns_mut() {
+        let mut doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let column = doc.columns_mut().iter_mut().find(|col| col.header.0 == "all_string").unwrap();
+        column.map_values_in_place(|value| CellValue::Text(format!("20{value}")));
+        let values = doc.column("all_string").unwrap().as_values();
+        assert_eq!(values[0], &CellValue::from("20Q1"));
+
+        doc.columns_mut()[1] = Column::from_values("short", vec![CellValue::Int(1)]);
+        let second_row = doc.rows().nth(1).unwrap();
+        assert_eq!(second_row[1], &CellValue::Null);
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert!((doc.column("all_string").unwrap().compression_ratio() - 4. / 12.).abs() < 1e-9);
+        assert_eq!(doc.column("all_int").unwrap().compression_ratio(), 1.);
+        assert_eq!(Column::from_values("empty", vec![]).compression_ratio(), 0.);
+    }
+
+    #[test]
+    fn test_columns_by_tag() {
+        let names = |doc: &QvdDocument, tag| doc.columns_by_tag(tag).iter().map(|col| col.header.0.clone()).collect::<Vec<_>>();
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(doc.metadata().field_tags("all_int"), ["$numeric", "$integer"]);
+        assert!(doc.metadata().field_tags("all Null").is_empty());
+        assert_eq!(names(&doc, "$text"), ["all_string", "all_float"]);
+        assert!(names(&doc, "$key").is_empty());
+
+        let mut bytes = std::fs::read("tests/test_file.qvd").unwrap();
+        let integer = b"<String>$integer</String>";
+        let pos = bytes.windows(integer.len()).position(|w| w == integer).unwrap();
+        bytes.splice(pos..pos + integer.len(), b"<String>$key</String>".iter().copied());
+        let keyed = QvdDocument::from_bytes(&bytes).unwrap();
+        assert_eq!(names(&keyed, "$key"), ["all_int"]);
+    }
+
+    #[test]
+    fn test_index_columns() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(doc["all_string"].header(), Header::from("all_string"));
+        assert_eq!(&doc[2], doc.column("all_float").unwrap());
+        assert!(doc.column_at(5).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "no column named 'missing'")]
+    fn test_index_missing_column() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let _ = &doc["missing"];
+    }
+
+    #[test]
+    #[should_panic(expected = "column index 5 out of range for 5 columns")]
+    fn test_index_column_out_of_range() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let _ = &doc[5];
+    }
+
+    #[test]
+    fn test_read_sample() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let sample = QvdDocument::read_sample("tests/test_file.qvd", 5, 42).unwrap();
+        assert_eq!(sample.row_count(), 5);
+        assert_eq!(sample.columns(), QvdDocument::read_sample("tests/test_file.qvd", 5, 42).unwrap().columns());
+        assert_ne!(sample.columns(), QvdDocument::read_sample("tests/test_file.qvd", 5, 7).unwrap().columns());
+
+        // all_int holds the row number, so every sampled row is a row of the file, in order
+        let ids: Vec<_> = sample["all_int"].as_values().into_iter().map(|v| i32::try_from(v).unwrap()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]), "{ids:?}");
+        let full_rows: Vec<_> = doc.rows().collect();
+        for (row, id) in sample.rows().zip(&ids) {
+            assert_eq!(row, full_rows[*id as usize - 1]);
+        }
+
+        let all = QvdDocument::read_sample("tests/test_file.qvd", 100, 1).unwrap();
+        assert_eq!(all.columns(), doc.columns());
+        assert_eq!(QvdDocument::read_sample("tests/test_file.qvd", 0, 1).unwrap().row_count(), 0);
+    }
+
+    #[test]
+    fn test_read_dictionaries() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let dictionaries = QvdDocument::read_dictionaries("tests/test_file.qvd").unwrap();
+        assert_eq!(dictionaries.len(), doc.columns().len());
+        for ((name, symbols), col) in dictionaries.iter().zip(doc.columns()) {
+            assert_eq!(name, &col.header.0);
+            assert_eq!(symbols, &col.symbols);
+        }
+        assert_eq!(dictionaries[1].1, vec!["Q1".into(), "Q2".into(), "Q3".into(), "Q4".into()]);
+    }
+
+    #[test]
+    fn test_rows_named() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let first = doc.rows_named().next().unwrap();
+        assert_eq!(first["all_string"], &CellValue::from("Q1"));
+        assert_eq!(first.keys().copied().collect::<Vec<_>>(), doc.column_names());
+        assert_eq!(doc.rows_named().count(), 12);
+    }
+
+    #[test]
+    fn test_qvd_table() {
+        fn first_row(table: &impl QvdTable) -> Vec<(&str, Option<CellValue>)> {
+            table.column_names().into_iter().map(|name| (name, table.cell(0, name))).collect()
+        }
+
+        let doc = QvdDocument::read("tests/floats.qvd").unwrap();
+        assert_eq!(first_row(&doc), vec![("Id", doc["Id"].get(0)), ("Number", doc["Number"].get(0))]);
+        assert_eq!(QvdTable::row_count(&doc), 3);
+        assert!(QvdTable::column(&doc, "Id").is_some());
+        assert_eq!(doc.cell(3, "Id"), None);
+        assert_eq!(doc.cell(0, "missing"), None);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut values: Vec<CellValue> = (0..=8).map(|i| CellValue::Float(i as f64)).collect();
+        values.extend([CellValue::Null, CellValue::Float(8.)]);
+        let column = Column::from_values("x", values);
+        assert_eq!(column.histogram(4).unwrap(), vec![(0., 2., 2), (2., 4., 2), (4., 6., 2), (6., 8., 4)]);
+        assert_eq!(column.histogram(1).unwrap(), vec![(0., 8., 10)]);
+        assert_eq!(column.histogram(0).unwrap_err().kind(), &QvdErrorKind::InvalidValue);
+
+        let constant = Column::from_values("c", vec![CellValue::Int(3); 3]);
+        assert_eq!(constant.histogram(2).unwrap(), vec![(3., 3., 3), (3., 3., 0)]);
+
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        assert_eq!(doc["all_string"].histogram(4).unwrap_err().kind(), &QvdErrorKind::NotNumeric);
+    }
+
+    #[test]
+    fn test_find_null_row_indexes() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let some_null = doc.column("some_null").unwrap();
+        assert_eq!(some_null.find_null_row_indexes(), vec![3, 4, 5]);
+        assert_eq!(doc.find_row_indexes("some_null", CellValue::Null), vec![3, 4, 5]);
+        assert_eq!(doc.column("all Null").unwrap().find_null_row_indexes().len(), 12);
+        assert!(doc.column("all_int").unwrap().find_null_row_indexes().is_empty());
+    }
+
+    #[test]
+    fn test_rows_chunked() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let chunks: Vec<_> = doc.rows_chunked(5).collect();
+        assert_eq!(chunks.iter().map(Vec::len).collect::<Vec<_>>(), vec![5, 5, 2]);
+        assert_eq!(chunks.concat(), doc.rows().collect::<Vec<_>>());
+        assert_eq!(doc.rows_chunked(12).count(), 1);
+        assert_eq!(doc.rows_chunked(0).count(), 12);
+    }
+
+    #[test]
+    fn test_to_records() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let records = doc.to_records();
+        assert_eq!(records.len(), 12);
+        let first: Vec<_> = records[0].iter().map(|(name, value)| (name.as_str(), value)).collect();
+        assert_eq!(first, vec![
+            ("all Null", &CellValue::Null),
+            ("all_float", &CellValue::Float(1.1)),
+            ("all_int", &CellValue::Int(1)),
+            ("all_string", &CellValue::from("Q1")),
+            ("some_null", &CellValue::Float(1.2)),
+        ]);
+
+        let ordered = doc.to_records_ordered();
+        assert_eq!(ordered[0].keys().map(String::as_str).collect::<Vec<_>>(), doc.column_names());
+        assert_eq!(ordered[0]["all_string"], records[0]["all_string"]);
     }
 
     #[test]
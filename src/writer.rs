@@ -0,0 +1,377 @@
+use std::{collections::HashMap, fs::File, io::{self, BufWriter, Write}, path::Path};
+
+use indexmap::IndexMap;
+
+use crate::{error::{QvdError, QvdErrorKind}, types::{CellValue, Column, Header, QvdDocument, NULL_INDEX}};
+
+/// Bias used for columns containing nulls, stored index 0 then decodes to `NULL_INDEX`.
+const NULL_BIAS: isize = NULL_INDEX;
+
+/// Builds a .qvd file from rows pushed one at a time. Symbol tables and row
+/// indexes are collected while pushing, the file is written by `finish`.
+pub struct QvdWriter {
+    table_name: String,
+    columns: Vec<Column>,
+    lookups: Vec<HashMap<OwnedSymbolKey, isize>>,
+}
+
+// Owned counterpart of `SymbolKey`, the writer owns its symbol tables while rows are pushed
+#[derive(PartialEq, Eq, Hash)]
+enum OwnedSymbolKey {
+    Text(String),
+    Int(i32),
+    Float(u64),
+    Dual(u64, String),
+}
+
+impl QvdWriter {
+    pub fn new(table_name: &str, column_names: &[&str]) -> Self {
+        Self {
+            table_name: table_name.into(),
+            columns: column_names.iter().map(|&name| Column {
+                header: Header::from(name),
+                symbols: Vec::new(),
+                indexes: Vec::new(),
+            }).collect(),
+            lookups: column_names.iter().map(|_| HashMap::new()).collect(),
+        }
+    }
+
+    /// Adds one row, `row` must hold a value for every column.
+    pub fn push_row(&mut self, row: &[CellValue]) -> Result<(), QvdError> {
+        if row.len() != self.columns.len() {
+            return Err(QvdError::new(
+                QvdErrorKind::SchemaMismatch,
+                format!("row has {} values but the writer has {} columns", row.len(), self.columns.len()),
+            ));
+        }
+        for ((column, lookup), value) in self.columns.iter_mut().zip(&mut self.lookups).zip(row) {
+            let key = match value {
+                CellValue::Text(s) => OwnedSymbolKey::Text(s.clone()),
+                CellValue::Int(i) => OwnedSymbolKey::Int(*i),
+                CellValue::Float(f) => OwnedSymbolKey::Float(f.to_bits()),
+                CellValue::Dual(f, s) => OwnedSymbolKey::Dual(f.to_bits(), s.clone()),
+                CellValue::Null => {
+                    column.indexes.push(NULL_INDEX);
+                    continue;
+                }
+            };
+            let next = column.symbols.len() as isize;
+            let idx = *lookup.entry(key).or_insert_with(|| {
+                column.symbols.push(value.clone());
+                next
+            });
+            column.indexes.push(idx);
+        }
+        Ok(())
+    }
+
+    /// Writes the .qvd file with all rows pushed so far.
+    pub fn finish<W: Write>(self, writer: W) -> Result<(), QvdError> {
+        write_qvd(writer, &self.table_name, &self.columns, &IndexMap::new())
+    }
+}
+
+impl QvdDocument {
+    /// Writes the document in .qvd format.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), QvdError> {
+        write_qvd(writer, self.metadata().table_name(), self.columns(), &self.metadata().field_tags)
+    }
+
+    /// Writes the document to a .qvd file at `path`, replacing any existing
+    /// file. The counterpart of `read`.
+    pub fn write_to_path(&self, path: impl AsRef<Path>) -> Result<(), QvdError> {
+        let write_error = |err: io::Error| QvdError::new(QvdErrorKind::WriteFile, err.to_string());
+        let mut writer = BufWriter::new(File::create(path).map_err(write_error)?);
+        self.write(&mut writer)?;
+        writer.flush().map_err(write_error)
+    }
+}
+
+struct FieldLayout {
+    symbol_bytes: Vec<u8>,
+    // stored index for every symbol, None for symbols that are written as null
+    stored: Vec<Option<u64>>,
+    no_of_symbols: usize,
+    bit_offset: usize,
+    bit_width: usize,
+    bias: isize,
+}
+
+impl FieldLayout {
+    fn stored_index(&self, idx: isize) -> u64 {
+        usize::try_from(idx).ok()
+            .and_then(|i| self.stored.get(i).copied().flatten())
+            .unwrap_or(0)
+    }
+}
+
+pub(crate) fn write_qvd<W: Write>(
+    mut writer: W,
+    table_name: &str,
+    columns: &[Column],
+    field_tags: &IndexMap<String, Vec<String>>,
+) -> Result<(), QvdError> {
+    let no_of_records = columns.first().map_or(0, |col| col.indexes.len());
+    if columns.iter().any(|col| col.indexes.len() != no_of_records) {
+        return Err(QvdError::new(QvdErrorKind::SchemaMismatch, "all columns must have the same number of rows"));
+    }
+
+    let mut bit_offset = 0;
+    let mut fields = Vec::with_capacity(columns.len());
+    for column in columns {
+        let field = layout_field(column, bit_offset)?;
+        bit_offset += field.bit_width;
+        fields.push(field);
+    }
+    // Every record takes at least one byte so the row section can be chunked
+    let record_byte_size = bit_offset.div_ceil(8).max(1);
+    let symbols_len: usize = fields.iter().map(|field| field.symbol_bytes.len()).sum();
+
+    let xml = header_xml(table_name, columns, field_tags, &fields, record_byte_size, no_of_records, symbols_len);
+    write_sections(&mut writer, &xml, columns, &fields, record_byte_size, no_of_records)
+        .map_err(|err| QvdError::new(QvdErrorKind::WriteFile, err.to_string()))
+}
+
+fn layout_field(column: &Column, bit_offset: usize) -> Result<FieldLayout, QvdError> {
+    let mut symbol_bytes = Vec::new();
+    let mut stored = Vec::with_capacity(column.symbols.len());
+    let mut no_of_symbols = 0;
+    for symbol in &column.symbols {
+        match symbol {
+            CellValue::Int(i) => {
+                symbol_bytes.push(1);
+                symbol_bytes.extend_from_slice(&i.to_le_bytes());
+            }
+            CellValue::Float(f) => {
+                symbol_bytes.push(2);
+                symbol_bytes.extend_from_slice(&f.to_le_bytes());
+            }
+            CellValue::Text(s) => {
+                check_text(column, s)?;
+                // The reader takes numeric string symbols for numbers, so text
+                // such as "007" is written as a dual to keep its spelling
+                if let Ok(int) = s.parse::<i32>() {
+                    symbol_bytes.push(5);
+                    symbol_bytes.extend_from_slice(&int.to_le_bytes());
+                } else if let Ok(float) = s.parse::<f64>() {
+                    symbol_bytes.push(6);
+                    symbol_bytes.extend_from_slice(&float.to_le_bytes());
+                } else {
+                    symbol_bytes.push(4);
+                }
+                symbol_bytes.extend_from_slice(s.as_bytes());
+                symbol_bytes.push(0);
+            }
+            CellValue::Dual(f, s) => {
+                check_text(column, s)?;
+                symbol_bytes.push(6);
+                symbol_bytes.extend_from_slice(&f.to_le_bytes());
+                symbol_bytes.extend_from_slice(s.as_bytes());
+                symbol_bytes.push(0);
+            }
+            CellValue::Null => {
+                stored.push(None);
+                continue;
+            }
+        }
+        stored.push(Some(no_of_symbols as u64));
+        no_of_symbols += 1;
+    }
+
+    let has_null = column.indexes.iter().any(|&idx| {
+        usize::try_from(idx).ok().and_then(|i| stored.get(i).copied().flatten()).is_none()
+    });
+    let bias = if has_null { NULL_BIAS } else { 0 };
+    if has_null {
+        stored.iter_mut().flatten().for_each(|idx| *idx += bias.unsigned_abs() as u64);
+    }
+    let max_stored = (no_of_symbols as u64 + bias.unsigned_abs() as u64).saturating_sub(1);
+    let bit_width = (u64::BITS - max_stored.leading_zeros()) as usize;
+
+    Ok(FieldLayout { symbol_bytes, stored, no_of_symbols, bit_offset, bit_width, bias })
+}
+
+// Text symbols are null terminated, so they can't contain a null byte themselves
+fn check_text(column: &Column, s: &str) -> Result<(), QvdError> {
+    if s.contains('\0') {
+        return Err(QvdError::new(
+            QvdErrorKind::WriteFile,
+            format!("text in column '{}' contains a null byte", column.header.0),
+        ));
+    }
+    Ok(())
+}
+
+fn write_sections<W: Write>(
+    writer: &mut W,
+    xml: &str,
+    columns: &[Column],
+    fields: &[FieldLayout],
+    record_byte_size: usize,
+    no_of_records: usize,
+) -> io::Result<()> {
+    writer.write_all(xml.as_bytes())?;
+    writer.write_all(b"\r\n\0")?;
+    for field in fields {
+        writer.write_all(&field.symbol_bytes)?;
+    }
+    let mut record = vec![0u8; record_byte_size];
+    for row in 0..no_of_records {
+        record.fill(0);
+        for (column, field) in columns.iter().zip(fields) {
+            let value = field.stored_index(column.indexes[row]);
+            // Records are little endian bit fields
+            for bit in 0..field.bit_width {
+                if (value >> bit) & 1 == 1 {
+                    let pos = field.bit_offset + bit;
+                    record[pos / 8] |= 1 << (pos % 8);
+                }
+            }
+        }
+        writer.write_all(&record)?;
+    }
+    writer.flush()
+}
+
+fn header_xml(
+    table_name: &str,
+    columns: &[Column],
+    field_tags: &IndexMap<String, Vec<String>>,
+    fields: &[FieldLayout],
+    record_byte_size: usize,
+    no_of_records: usize,
+    symbols_len: usize,
+) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\r\n<QvdTableHeader>\r\n");
+    xml.push_str("  <CreatorDoc></CreatorDoc>\r\n");
+    xml.push_str(&format!("  <TableName>{}</TableName>\r\n", escape_xml(table_name)));
+    xml.push_str("  <Fields>\r\n");
+    let mut offset = 0;
+    for (column, field) in columns.iter().zip(fields) {
+        xml.push_str("    <QvdFieldHeader>\r\n");
+        xml.push_str(&format!("      <FieldName>{}</FieldName>\r\n", escape_xml(&column.header.0)));
+        xml.push_str(&format!("      <BitOffset>{}</BitOffset>\r\n", field.bit_offset));
+        xml.push_str(&format!("      <BitWidth>{}</BitWidth>\r\n", field.bit_width));
+        xml.push_str(&format!("      <Bias>{}</Bias>\r\n", field.bias));
+        xml.push_str("      <NumberFormat>\r\n        <Type>UNKNOWN</Type>\r\n        <nDec>0</nDec>\r\n        <UseThou>0</UseThou>\r\n        <Fmt></Fmt>\r\n        <Dec></Dec>\r\n        <Thou></Thou>\r\n      </NumberFormat>\r\n");
+        xml.push_str(&format!("      <NoOfSymbols>{}</NoOfSymbols>\r\n", field.no_of_symbols));
+        xml.push_str(&format!("      <Offset>{offset}</Offset>\r\n"));
+        xml.push_str(&format!("      <Length>{}</Length>\r\n", field.symbol_bytes.len()));
+        xml.push_str("      <Comment></Comment>\r\n      <Tags>");
+        let tags = field_tags.get(&column.header.0).map_or(&[][..], Vec::as_slice);
+        for tag in tags {
+            xml.push_str(&format!("\r\n        <String>{}</String>", escape_xml(tag)));
+        }
+        if !tags.is_empty() {
+            xml.push_str("\r\n      ");
+        }
+        xml.push_str("</Tags>\r\n");
+        xml.push_str("    </QvdFieldHeader>\r\n");
+        offset += field.symbol_bytes.len();
+    }
+    xml.push_str("  </Fields>\r\n");
+    xml.push_str("  <Compression></Compression>\r\n");
+    xml.push_str(&format!("  <RecordByteSize>{record_byte_size}</RecordByteSize>\r\n"));
+    xml.push_str(&format!("  <NoOfRecords>{no_of_records}</NoOfRecords>\r\n"));
+    xml.push_str(&format!("  <Offset>{symbols_len}</Offset>\r\n"));
+    xml.push_str(&format!("  <Length>{}</Length>\r\n", no_of_records * record_byte_size));
+    xml.push_str("  <Lineage></Lineage>\r\n  <Comment></Comment>\r\n");
+    xml.push_str("</QvdTableHeader>");
+    xml
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{reader::{read_qvd_from_reader, QvdReadOptions}, test_util::TempFile};
+
+    use super::*;
+
+    #[test]
+    fn test_push_rows_and_read_back() {
+        let mut writer = QvdWriter::new("pushed", &["id", "name & <alias>", "score"]);
+        let rows = vec![
+            vec![1.into(), "a".into(), 1.5.into()],
+            vec![2.into(), "b & c".into(), CellValue::Null],
+            vec![3.into(), "a".into(), 2.25.into()],
+            vec![1.into(), CellValue::Null, 1.5.into()],
+        ];
+        for row in &rows {
+            writer.push_row(row).unwrap();
+        }
+        assert!(writer.push_row(&[1.into()]).is_err());
+        let mut buf = Vec::new();
+        writer.finish(&mut buf).unwrap();
+
+        let (metadata, columns) = read_qvd_from_reader(&mut buf.as_slice(), &QvdReadOptions::default()).unwrap();
+        assert_eq!(metadata.table_name(), "pushed");
+        assert_eq!(metadata.no_of_records(), 4);
+        let doc = QvdDocument::from_columns(columns);
+        assert_eq!(doc.rows_owned().collect::<Vec<_>>(), rows);
+        assert_eq!(doc.columns()[1].header(), Header::from("name & <alias>"));
+        assert_eq!(doc.columns()[1].symbols, vec!["a".into(), "b & c".into()]);
+    }
+
+    #[test]
+    fn test_write_document_round_trip() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let mut buf = Vec::new();
+        doc.write(&mut buf).unwrap();
+        let written = QvdDocument::from_bytes(&buf).unwrap();
+        assert_eq!(written.columns(), doc.columns());
+        assert_eq!(written.metadata().field_tags("all_int"), ["$numeric", "$integer"]);
+        assert_eq!(written.metadata().field_tags("all_int"), doc.metadata().field_tags("all_int"));
+    }
+
+    #[test]
+    fn test_numeric_looking_text_round_trip() {
+        let column = Column::from_values("code", vec!["007".into(), "1e3".into(), "+5".into(), 7.into(), 1.5.into()]);
+        let doc = QvdDocument::from_columns(vec![column]);
+        let mut buf = Vec::new();
+        doc.write(&mut buf).unwrap();
+        let (_, columns) = read_qvd_from_reader(&mut buf.as_slice(), &QvdReadOptions::default()).unwrap();
+        let expected = vec![
+            CellValue::Dual(7., "007".into()),
+            CellValue::Dual(1000., "1e3".into()),
+            CellValue::Dual(5., "+5".into()),
+            CellValue::Int(7),
+            CellValue::Float(1.5),
+        ];
+        assert_eq!(columns[0].symbols, expected);
+        assert_eq!(columns[0].indexes, doc.columns()[0].indexes);
+    }
+
+    #[test]
+    fn test_write_to_path() {
+        let doc = QvdDocument::read("tests/test_file.qvd").unwrap();
+        let file = TempFile::new("write_to_path", &[]);
+        doc.write_to_path(file.path()).unwrap();
+        let written = QvdDocument::read(file.path()).unwrap();
+        assert_eq!(written.metadata().table_name(), doc.metadata().table_name());
+        assert_eq!(written.columns(), doc.columns());
+
+        let err = doc.write_to_path(std::env::temp_dir().join("rqvd_missing_dir").join("out.qvd")).unwrap_err();
+        assert_eq!(err.kind(), &QvdErrorKind::WriteFile);
+    }
+
+    #[test]
+    fn test_layout_field() {
+        let column = Column::from_values("x", vec![5.into(), CellValue::Null, 6.into(), 7.into()]);
+        let field = layout_field(&column, 3).unwrap();
+        assert_eq!(field.bias, -2);
+        // stored indexes 0 (null), 2, 3, 4
+        assert_eq!(field.bit_width, 3);
+        assert_eq!(field.stored, vec![Some(2), Some(3), Some(4)]);
+
+        let single = Column::from_values("y", vec!["only".into(), "only".into()]);
+        let field = layout_field(&single, 0).unwrap();
+        assert_eq!((field.bias, field.bit_width), (0, 0));
+    }
+}
@@ -0,0 +1,45 @@
+use proptest::prelude::*;
+use rqvd::QvdDocument;
+
+const FIXTURE: &[u8] = include_bytes!("test_file.qvd");
+
+// First byte after the header's null terminator
+fn data_start() -> usize {
+    FIXTURE.iter().position(|&b| b == 0).unwrap() + 1
+}
+
+proptest! {
+    #[test]
+    fn random_bytes(bytes in prop::collection::vec(any::<u8>(), 0..1024)) {
+        let _ = QvdDocument::try_read(&bytes[..]);
+    }
+
+    #[test]
+    fn truncated_file(len in 0..FIXTURE.len()) {
+        prop_assert!(QvdDocument::try_read(&FIXTURE[..len]).is_err());
+    }
+
+    #[test]
+    fn mutated_file(mutations in prop::collection::vec((0..FIXTURE.len(), any::<u8>()), 1..16)) {
+        let mut bytes = FIXTURE.to_vec();
+        for (pos, byte) in mutations {
+            bytes[pos] = byte;
+        }
+        let _ = QvdDocument::try_read(&bytes[..]);
+    }
+
+    #[test]
+    fn mutated_data_section(mutations in prop::collection::vec((data_start()..FIXTURE.len(), any::<u8>()), 1..16)) {
+        let mut bytes = FIXTURE.to_vec();
+        for (pos, byte) in mutations {
+            bytes[pos] = byte;
+        }
+        let _ = QvdDocument::try_read(&bytes[..]);
+    }
+}
+
+#[test]
+fn reads_intact_file() {
+    let doc = QvdDocument::try_read(FIXTURE).unwrap();
+    assert_eq!(doc.row_count(), 12);
+}